@@ -0,0 +1,229 @@
+use std::ops::Range;
+
+use clap::ValueEnum;
+
+use crate::analysis::spectrum::Spectrum;
+
+/// Nominal ISO 266 octave band center frequencies in Hz.
+const OCTAVE_CENTERS: [f32; 11] = [
+    16.0, 31.5, 63.0, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0,
+];
+
+/// Nominal ISO 266 third octave band center frequencies in Hz.
+const THIRD_OCTAVE_CENTERS: [f32; 31] = [
+    16.0, 20.0, 25.0, 31.5, 40.0, 50.0, 63.0, 80.0, 100.0, 125.0, 160.0, 200.0, 250.0, 315.0,
+    400.0, 500.0, 630.0, 800.0, 1000.0, 1250.0, 1600.0, 2000.0, 2500.0, 3150.0, 4000.0, 5000.0,
+    6300.0, 8000.0, 10000.0, 12500.0, 16000.0,
+];
+
+/// How the frequency range is divided into bands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BandScale {
+    /// Bands of equal width in Hz.
+    Linear,
+    /// Bands of equal width on a logarithmic frequency axis.
+    Log,
+    /// The ISO 266 octave bands within the frequency range.
+    Octave,
+    /// The ISO 266 third octave bands within the frequency range.
+    ThirdOctave,
+    /// Bands of equal width on the Bark scale (Traunmüller).
+    Bark,
+    /// Bands of equal width on the Mel scale.
+    Mel,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BandConfig {
+    pub scale: BandScale,
+    /// Number of bands. Ignored by the octave scales, which have one band
+    /// per ISO center frequency in range.
+    pub count: usize,
+    /// Lower edge of the lowest band in Hz.
+    pub min_frequency: f32,
+    /// Upper edge of the highest band in Hz, capped at the Nyquist frequency.
+    pub max_frequency: f32,
+}
+
+impl Default for BandConfig {
+    fn default() -> Self {
+        BandConfig {
+            scale: BandScale::Log,
+            count: 32,
+            min_frequency: 20.0,
+            max_frequency: 20000.0,
+        }
+    }
+}
+
+/// One band, with its edges and center in Hz.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Band {
+    pub low: f32,
+    pub center: f32,
+    pub high: f32,
+}
+
+impl BandScale {
+    /// The bands of this scale between `min` and `max` Hz.
+    pub fn bands(self, count: usize, min: f32, max: f32) -> Vec<Band> {
+        match self {
+            BandScale::Linear => warped_bands(count, min, max, |hz| hz, |hz| hz),
+            BandScale::Log => warped_bands(count, min, max, f32::ln, f32::exp),
+            BandScale::Octave => iso_bands(&OCTAVE_CENTERS, 1.0, min, max),
+            BandScale::ThirdOctave => iso_bands(&THIRD_OCTAVE_CENTERS, 1.0 / 3.0, min, max),
+            BandScale::Bark => warped_bands(count, min, max, hz_to_bark, bark_to_hz),
+            BandScale::Mel => warped_bands(count, min, max, hz_to_mel, mel_to_hz),
+        }
+    }
+}
+
+/// `count` bands of equal width after mapping frequencies with `warp`.
+fn warped_bands(
+    count: usize,
+    min: f32,
+    max: f32,
+    warp: fn(f32) -> f32,
+    unwarp: fn(f32) -> f32,
+) -> Vec<Band> {
+    let (low, high) = (warp(min), warp(max));
+    let edge = |index: usize| unwarp(low + (high - low) * index as f32 / count as f32);
+
+    (0..count)
+        .map(|index| Band {
+            low: edge(index),
+            center: unwarp(low + (high - low) * (index as f32 + 0.5) / count as f32),
+            high: edge(index + 1),
+        })
+        .collect()
+}
+
+/// The bands around the nominal `centers` that lie within `min..=max`.
+/// Edges are computed from the exact base 10 center frequencies, the
+/// nominal ones are only rounded for display.
+fn iso_bands(centers: &[f32], fraction: f32, min: f32, max: f32) -> Vec<Band> {
+    centers
+        .iter()
+        .map(|&nominal| {
+            // Nominal centers are within 1% of 1000 Hz * 10^(k/10).
+            let k = (10.0 * (nominal / 1000.0).log10()).round();
+            let exact = 1000.0 * 10f32.powf(k / 10.0);
+            let half_width = 10f32.powf(3.0 * fraction / 20.0);
+            Band {
+                low: exact / half_width,
+                center: nominal,
+                high: exact * half_width,
+            }
+        })
+        .filter(|band| band.center >= min && band.center <= max)
+        .collect()
+}
+
+fn hz_to_bark(hz: f32) -> f32 {
+    26.81 * hz / (1960.0 + hz) - 0.53
+}
+
+fn bark_to_hz(bark: f32) -> f32 {
+    1960.0 * (bark + 0.53) / (26.28 - bark)
+}
+
+fn hz_to_mel(hz: f32) -> f32 {
+    2595.0 * (1.0 + hz / 700.0).log10()
+}
+
+fn mel_to_hz(mel: f32) -> f32 {
+    700.0 * (10f32.powf(mel / 2595.0) - 1.0)
+}
+
+/// How a band's level is taken from the spectrum.
+#[derive(Debug, Clone)]
+enum Source {
+    /// The loudest of the bins centered inside the band.
+    Bins(Range<usize>),
+    /// No bin is centered inside the band, so the level is interpolated
+    /// between the two bins around its center.
+    Interpolated { bin: usize, fraction: f32 },
+}
+
+/// Aggregates the bins of [`Spectrum`]s into bands for bar displays.
+///
+/// Each band reads the loudest bin inside it, so a full scale sine shows as
+/// 0 dBFS regardless of band width. Low bands are often narrower than a
+/// bin; those are linearly interpolated instead of repeating or skipping
+/// bins.
+///
+/// The mapping is recomputed whenever the sample rate or FFT size of the
+/// spectra changes.
+pub struct BandMapper {
+    config: BandConfig,
+    layout: Option<(u32, usize)>,
+    bands: Vec<Band>,
+    sources: Vec<Source>,
+}
+
+impl BandMapper {
+    pub fn new(config: BandConfig) -> Self {
+        BandMapper {
+            config,
+            layout: None,
+            bands: Vec::new(),
+            sources: Vec::new(),
+        }
+    }
+
+    /// Linear band magnitudes of `spectrum`, from the lowest band up.
+    pub fn map(&mut self, spectrum: &Spectrum) -> Vec<f32> {
+        if self.layout != Some((spectrum.rate, spectrum.fft_size)) {
+            self.layout(spectrum);
+        }
+
+        let magnitudes = &spectrum.magnitudes;
+        self.sources
+            .iter()
+            .map(|source| match *source {
+                Source::Bins(ref bins) => {
+                    magnitudes[bins.clone()].iter().copied().fold(0.0, f32::max)
+                }
+                Source::Interpolated { bin, fraction } => {
+                    let next = magnitudes.get(bin + 1).copied().unwrap_or(0.0);
+                    magnitudes[bin] + (next - magnitudes[bin]) * fraction
+                }
+            })
+            .collect()
+    }
+
+    fn layout(&mut self, spectrum: &Spectrum) {
+        let nyquist = spectrum.rate as f32 / 2.0;
+        let bin_width = spectrum.rate as f32 / spectrum.fft_size as f32;
+        let last_bin = spectrum.magnitudes.len().saturating_sub(1);
+
+        // `clamp` needs a range that isn't empty.
+        let max = self
+            .config
+            .max_frequency
+            .min(nyquist)
+            .max(f32::MIN_POSITIVE);
+        let min = self.config.min_frequency.clamp(f32::MIN_POSITIVE, max);
+        self.bands = self.config.scale.bands(self.config.count, min, max);
+
+        self.sources = self
+            .bands
+            .iter()
+            .map(|band| {
+                let first = (band.low / bin_width).ceil() as usize;
+                let end = ((band.high / bin_width).ceil() as usize).min(last_bin + 1);
+                if first < end {
+                    return Source::Bins(first..end);
+                }
+
+                let position = (band.center / bin_width).min(last_bin as f32);
+                Source::Interpolated {
+                    bin: position.floor() as usize,
+                    fraction: position.fract(),
+                }
+            })
+            .collect();
+
+        self.layout = Some((spectrum.rate, spectrum.fft_size));
+    }
+}
@@ -0,0 +1,403 @@
+use std::{collections::VecDeque, f64::consts::PI, fmt};
+
+use pulse::channelmap::Position;
+use serde::Serialize;
+
+use crate::{analysis::meter::TruePeak, decode::AudioBlock};
+
+/// Blocks of gating and loudness range are 100 ms apart.
+const STEPS_PER_SECOND: u32 = 10;
+/// Momentary loudness covers 400 ms.
+const MOMENTARY_STEPS: usize = 4;
+/// Short-term loudness covers 3 s.
+const SHORT_TERM_STEPS: usize = 30;
+/// Blocks quieter than this are ignored by integrated loudness and LRA.
+const ABSOLUTE_GATE: f64 = -70.0;
+/// Integrated loudness ignores blocks this far below the ungated level.
+const INTEGRATED_RELATIVE_GATE: f64 = -10.0;
+/// Loudness range ignores blocks this far below the ungated level.
+const RANGE_RELATIVE_GATE: f64 = -20.0;
+/// Width of a gating histogram bin in LU.
+const BIN_WIDTH: f64 = 0.1;
+/// Gating histogram bins from the absolute gate up to +30 LUFS. Louder
+/// blocks go into the last one.
+const BINS: usize = 1000;
+
+/// A biquad filter in direct form I.
+#[derive(Debug, Clone, Copy, Default)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, input: f64) -> f64 {
+        let output = self.b[0] * input + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+        self.x = [input, self.x[0]];
+        self.y = [output, self.y[0]];
+        output
+    }
+}
+
+/// The two stage K-weighting filter of ITU-R BS.1770, a high shelf for the
+/// acoustic effect of the head followed by a high pass, designed for any
+/// sample rate.
+#[derive(Debug, Clone, Copy)]
+struct KWeighting {
+    shelf: Biquad,
+    high_pass: Biquad,
+}
+
+impl KWeighting {
+    fn new(rate: u32) -> Self {
+        let rate = rate as f64;
+
+        let (f0, gain, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+        let k = (PI * f0 / rate).tan();
+        let vh = 10f64.powf(gain / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = Biquad {
+            b: [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            ..Biquad::default()
+        };
+
+        let (f0, q) = (38.13547087602444, 0.5003270373238773);
+        let k = (PI * f0 / rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+        let high_pass = Biquad {
+            b: [1.0, -2.0, 1.0],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            ..Biquad::default()
+        };
+
+        KWeighting { shelf, high_pass }
+    }
+
+    fn process(&mut self, sample: f32) -> f64 {
+        self.high_pass.process(self.shelf.process(sample as f64))
+    }
+}
+
+/// The BS.1770 weight of a channel: surround channels count 1.5 dB more,
+/// LFE not at all.
+fn channel_weight(position: Position) -> f64 {
+    match position {
+        Position::Lfe => 0.0,
+        Position::RearLeft | Position::RearRight | Position::SideLeft | Position::SideRight => 1.41,
+        _ => 1.0,
+    }
+}
+
+/// Loudness in LUFS of a weighted mean square.
+fn to_lufs(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+/// Blocks above the absolute gate, binned by loudness in 0.1 LU steps the
+/// way libebur128 does it, so gating costs the same however long the
+/// measurement runs. Each bin keeps the exact power sum of its blocks, so
+/// only the relative gate is rounded to a bin edge.
+#[derive(Debug, Clone)]
+struct Histogram {
+    counts: Vec<u64>,
+    powers: Vec<f64>,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Histogram {
+            counts: vec![0; BINS],
+            powers: vec![0.0; BINS],
+        }
+    }
+
+    /// The bin of blocks as loud as `loudness`, which is above the
+    /// absolute gate.
+    fn bin(loudness: f64) -> usize {
+        (((loudness - ABSOLUTE_GATE) / BIN_WIDTH) as usize).min(BINS - 1)
+    }
+
+    /// Loudness in the middle of `bin`.
+    fn center(bin: usize) -> f64 {
+        ABSOLUTE_GATE + (bin as f64 + 0.5) * BIN_WIDTH
+    }
+
+    fn add(&mut self, power: f64) {
+        let loudness = to_lufs(power);
+        if loudness > ABSOLUTE_GATE {
+            let bin = Histogram::bin(loudness);
+            self.counts[bin] += 1;
+            self.powers[bin] += power;
+        }
+    }
+
+    /// Mean power of the blocks in `bin` and above.
+    fn mean_from(&self, bin: usize) -> Option<f64> {
+        let count: u64 = self.counts[bin..].iter().sum();
+        (count > 0).then(|| self.powers[bin..].iter().sum::<f64>() / count as f64)
+    }
+
+    /// The first bin passing the gate `relative_gate` LU below the mean of
+    /// all blocks.
+    fn gate(&self, relative_gate: f64) -> Option<usize> {
+        let threshold = to_lufs(self.mean_from(0)?) + relative_gate;
+        Some(if threshold > ABSOLUTE_GATE {
+            Histogram::bin(threshold)
+        } else {
+            0
+        })
+    }
+
+    /// Loudness of the block at percentile `p` of those in `bin` and
+    /// above, as the middle of its bin.
+    fn percentile(&self, bin: usize, p: f64) -> Option<f64> {
+        let count: u64 = self.counts[bin..].iter().sum();
+        let index = (count.checked_sub(1)? as f64 * p).round() as u64;
+        let mut seen = 0;
+        (bin..BINS)
+            .find(|&bin| {
+                seen += self.counts[bin];
+                seen > index
+            })
+            .map(Histogram::center)
+    }
+}
+
+/// Loudness values at one point in time, in LUFS. `None` until enough audio
+/// came in for the measurement.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct LoudnessReading {
+    /// Seconds of audio measured.
+    pub time: f64,
+    pub momentary: Option<f64>,
+    pub short_term: Option<f64>,
+    pub integrated: Option<f64>,
+}
+
+/// The result of a whole measurement.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct LoudnessSummary {
+    /// Seconds of audio measured.
+    pub duration: f64,
+    /// Integrated loudness in LUFS.
+    pub integrated: Option<f64>,
+    /// Loudness range in LU.
+    pub range: Option<f64>,
+    pub max_momentary: Option<f64>,
+    pub max_short_term: Option<f64>,
+    /// Highest true peak of any channel, in dBTP.
+    pub true_peak: Option<f64>,
+    /// Integrated loudness the measurement is compared against, in LUFS.
+    pub target: f64,
+}
+
+impl fmt::Display for LoudnessSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = |value: Option<f64>, unit: &str| match value {
+            Some(value) => format!("{:.1} {}", value, unit),
+            None => "-".to_string(),
+        };
+
+        writeln!(f, "Measured {:.1} s of audio.", self.duration)?;
+        match self.integrated {
+            Some(integrated) => writeln!(
+                f,
+                "Integrated loudness: {:.1} LUFS ({:+.1} LU from the {:.1} LUFS target)",
+                integrated,
+                integrated - self.target,
+                self.target
+            )?,
+            None => writeln!(f, "Integrated loudness: -")?,
+        }
+        writeln!(f, "Loudness range:      {}", value(self.range, "LU"))?;
+        writeln!(
+            f,
+            "Max momentary:       {}",
+            value(self.max_momentary, "LUFS")
+        )?;
+        writeln!(
+            f,
+            "Max short-term:      {}",
+            value(self.max_short_term, "LUFS")
+        )?;
+        write!(f, "True peak:           {}", value(self.true_peak, "dBTP"))
+    }
+}
+
+/// Loudness measurement after ITU-R BS.1770 and EBU R128: K-weighted
+/// momentary, short-term and gated integrated loudness, plus the loudness
+/// range of EBU Tech 3342.
+///
+/// Audio is summed up in 100 ms steps. Momentary and short-term loudness
+/// are the mean over the last 4 and 30 steps, and every step adds one
+/// gating block for integrated loudness and one short-term value for the
+/// loudness range to their histograms.
+pub struct LoudnessMeter {
+    target: f64,
+    rate: u32,
+    positions: Vec<Position>,
+    filters: Vec<KWeighting>,
+    true_peaks: Vec<TruePeak>,
+    max_true_peak: f32,
+    step_len: usize,
+    step_fill: usize,
+    step_power: f64,
+    /// Weighted mean square of the last [`SHORT_TERM_STEPS`] steps.
+    steps: VecDeque<f64>,
+    total_steps: usize,
+    momentary_blocks: Histogram,
+    short_term_blocks: Histogram,
+    max_momentary: Option<f64>,
+    max_short_term: Option<f64>,
+}
+
+impl LoudnessMeter {
+    /// A meter comparing integrated loudness against `target` LUFS.
+    pub fn new(target: f64) -> Self {
+        LoudnessMeter {
+            target,
+            rate: 0,
+            positions: Vec::new(),
+            filters: Vec::new(),
+            true_peaks: Vec::new(),
+            max_true_peak: 0.0,
+            step_len: 0,
+            step_fill: 0,
+            step_power: 0.0,
+            steps: VecDeque::with_capacity(SHORT_TERM_STEPS),
+            total_steps: 0,
+            momentary_blocks: Histogram::new(),
+            short_term_blocks: Histogram::new(),
+            max_momentary: None,
+            max_short_term: None,
+        }
+    }
+
+    /// Measures `block`, calling `on_step` with the meter every 100 ms of
+    /// audio.
+    ///
+    /// A change of rate or channel layout restarts the filters and the
+    /// momentary and short-term windows. Integrated loudness and range
+    /// carry on, the measurement is still of the same program.
+    pub fn push(&mut self, block: &AudioBlock, mut on_step: impl FnMut(&LoudnessMeter)) {
+        if block.rate != self.rate || block.positions != self.positions {
+            self.rate = block.rate;
+            self.positions = block.positions.clone();
+            self.filters = vec![KWeighting::new(block.rate); block.channels.len()];
+            self.true_peaks = vec![TruePeak::default(); block.channels.len()];
+            self.step_len = (block.rate / STEPS_PER_SECOND).max(1) as usize;
+            self.step_fill = 0;
+            self.step_power = 0.0;
+            self.steps.clear();
+        }
+
+        let weights: Vec<f64> = self.positions.iter().map(|&p| channel_weight(p)).collect();
+        for frame in 0..block.len() {
+            for (channel, samples) in block.channels.iter().enumerate() {
+                let sample = samples[frame];
+                let weighted = self.filters[channel].process(sample);
+                self.step_power += weights[channel] * weighted * weighted;
+                self.max_true_peak = self
+                    .max_true_peak
+                    .max(self.true_peaks[channel].process(sample));
+            }
+
+            self.step_fill += 1;
+            if self.step_fill == self.step_len {
+                self.finish_step();
+                on_step(self);
+            }
+        }
+    }
+
+    fn finish_step(&mut self) {
+        if self.steps.len() == SHORT_TERM_STEPS {
+            self.steps.pop_front();
+        }
+        self.steps.push_back(self.step_power / self.step_len as f64);
+        self.step_fill = 0;
+        self.step_power = 0.0;
+        self.total_steps += 1;
+
+        if let Some(power) = self.window_power(MOMENTARY_STEPS) {
+            self.momentary_blocks.add(power);
+            let loudness = to_lufs(power);
+            self.max_momentary = Some(self.max_momentary.map_or(loudness, |max| max.max(loudness)));
+        }
+        if let Some(power) = self.window_power(SHORT_TERM_STEPS) {
+            self.short_term_blocks.add(power);
+            let loudness = to_lufs(power);
+            self.max_short_term = Some(
+                self.max_short_term
+                    .map_or(loudness, |max| max.max(loudness)),
+            );
+        }
+    }
+
+    /// Mean power of the last `steps` steps, once there are that many.
+    fn window_power(&self, steps: usize) -> Option<f64> {
+        if self.steps.len() < steps {
+            return None;
+        }
+        let sum: f64 = self.steps.iter().rev().take(steps).sum();
+        Some(sum / steps as f64)
+    }
+
+    /// Loudness of the last 400 ms in LUFS.
+    pub fn momentary(&self) -> Option<f64> {
+        self.window_power(MOMENTARY_STEPS).map(to_lufs)
+    }
+
+    /// Loudness of the last 3 s in LUFS.
+    pub fn short_term(&self) -> Option<f64> {
+        self.window_power(SHORT_TERM_STEPS).map(to_lufs)
+    }
+
+    /// Gated loudness of everything measured so far in LUFS.
+    pub fn integrated(&self) -> Option<f64> {
+        let blocks = &self.momentary_blocks;
+        blocks
+            .mean_from(blocks.gate(INTEGRATED_RELATIVE_GATE)?)
+            .map(to_lufs)
+    }
+
+    /// Loudness range in LU: the spread between the 10th and 95th
+    /// percentile of the gated short-term loudness.
+    pub fn range(&self) -> Option<f64> {
+        let blocks = &self.short_term_blocks;
+        let gate = blocks.gate(RANGE_RELATIVE_GATE)?;
+        Some(blocks.percentile(gate, 0.95)? - blocks.percentile(gate, 0.10)?)
+    }
+
+    pub fn reading(&self) -> LoudnessReading {
+        LoudnessReading {
+            time: self.total_steps as f64 / STEPS_PER_SECOND as f64,
+            momentary: self.momentary(),
+            short_term: self.short_term(),
+            integrated: self.integrated(),
+        }
+    }
+
+    pub fn summary(&self) -> LoudnessSummary {
+        LoudnessSummary {
+            duration: self.total_steps as f64 / STEPS_PER_SECOND as f64,
+            integrated: self.integrated(),
+            range: self.range(),
+            max_momentary: self.max_momentary,
+            max_short_term: self.max_short_term,
+            true_peak: (self.max_true_peak > 0.0)
+                .then(|| 20.0 * (self.max_true_peak as f64).log10()),
+            target: self.target,
+        }
+    }
+}
@@ -0,0 +1,308 @@
+use std::time::Duration;
+
+use clap::ValueEnum;
+use pulse::channelmap::Position;
+
+use crate::decode::AudioBlock;
+
+/// Integration time of the RMS level.
+const RMS_INTEGRATION: Duration = Duration::from_millis(300);
+
+/// The ITU-R BS.1770-4 interpolation filter for 4x oversampling, split
+/// into its four phases of 12 taps.
+const TRUE_PEAK_PHASES: [[f32; 12]; 4] = [
+    [
+        0.001_708_984_4,
+        0.010_986_328,
+        -0.019_653_32,
+        0.033_203_125,
+        -0.059_448_242,
+        0.137_329_1,
+        0.972_167_97,
+        -0.102_294_92,
+        0.047_607_42,
+        -0.026_611_328,
+        0.014_892_578,
+        -0.008_300_781,
+    ],
+    [
+        -0.029_174_805,
+        0.029_296_875,
+        -0.051_757_812,
+        0.089_111_33,
+        -0.166_503_9,
+        0.465_087_9,
+        0.779_785_16,
+        -0.200_317_38,
+        0.101_562_5,
+        -0.058_227_54,
+        0.033_081_055,
+        -0.018_920_898,
+    ],
+    [
+        -0.018_920_898,
+        0.033_081_055,
+        -0.058_227_54,
+        0.101_562_5,
+        -0.200_317_38,
+        0.779_785_16,
+        0.465_087_9,
+        -0.166_503_9,
+        0.089_111_33,
+        -0.051_757_812,
+        0.029_296_875,
+        -0.029_174_805,
+    ],
+    [
+        -0.008_300_781,
+        0.014_892_578,
+        -0.026_611_328,
+        0.047_607_42,
+        -0.102_294_92,
+        0.972_167_97,
+        0.137_329_1,
+        -0.059_448_242,
+        0.033_203_125,
+        -0.019_653_32,
+        0.010_986_328,
+        0.001_708_984_4,
+    ],
+];
+
+/// Estimates the peak level between samples by 4x oversampling, as
+/// specified for true peak meters in ITU-R BS.1770.
+#[derive(Debug, Clone, Default)]
+pub struct TruePeak {
+    history: [f32; 12],
+}
+
+impl TruePeak {
+    /// Feeds one sample and returns the highest absolute value of the four
+    /// interpolated samples ending at it.
+    pub fn process(&mut self, sample: f32) -> f32 {
+        self.history.copy_within(..11, 1);
+        self.history[0] = sample;
+
+        TRUE_PEAK_PHASES
+            .iter()
+            .map(|taps| {
+                taps.iter()
+                    .zip(&self.history)
+                    .map(|(tap, sample)| tap * sample)
+                    .sum::<f32>()
+                    .abs()
+            })
+            .fold(sample.abs(), f32::max)
+    }
+}
+
+/// How fast a meter's reading falls back after a peak.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Release {
+    /// Exponential decay of the linear level with this time constant.
+    TimeConstant(Duration),
+    /// Linear decay in dB.
+    DbPerSecond(f32),
+}
+
+/// How a meter's reading follows the signal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ballistics {
+    /// Time constant of the rise towards a louder signal. Zero follows
+    /// instantly.
+    pub attack: Duration,
+    pub release: Release,
+    /// How long a peak is held before it starts to fall.
+    pub hold: Duration,
+    /// Whether the meter reads the oversampled true peak rather than the
+    /// samples.
+    pub true_peak: bool,
+}
+
+/// Standard meter ballistics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BallisticsPreset {
+    /// Volume unit meter, averaging with 300 ms rise and fall times.
+    Vu,
+    /// IEC 60268-10 Type I (DIN) peak programme meter: 5 ms integration,
+    /// falling 20 dB in 1.5 s.
+    PpmType1,
+    /// IEC 60268-10 Type II (BBC, EBU) peak programme meter: 10 ms
+    /// integration, falling 24 dB in 2.8 s.
+    PpmType2,
+    /// Digital true peak meter with instant attack and a 1.5 s peak hold.
+    Digital,
+}
+
+impl BallisticsPreset {
+    pub fn ballistics(self) -> Ballistics {
+        match self {
+            // A first order rise to 99% in 300 ms.
+            BallisticsPreset::Vu => Ballistics {
+                attack: Duration::from_millis(65),
+                release: Release::TimeConstant(Duration::from_millis(65)),
+                hold: Duration::ZERO,
+                true_peak: false,
+            },
+            // A 10 ms burst reads 1 dB below a steady tone.
+            BallisticsPreset::PpmType1 => Ballistics {
+                attack: Duration::from_micros(4500),
+                release: Release::DbPerSecond(20.0 / 1.5),
+                hold: Duration::ZERO,
+                true_peak: false,
+            },
+            // A 10 ms burst reads 2 dB below a steady tone.
+            BallisticsPreset::PpmType2 => Ballistics {
+                attack: Duration::from_micros(6300),
+                release: Release::DbPerSecond(24.0 / 2.8),
+                hold: Duration::ZERO,
+                true_peak: false,
+            },
+            BallisticsPreset::Digital => Ballistics {
+                attack: Duration::ZERO,
+                release: Release::DbPerSecond(20.0),
+                hold: Duration::from_millis(1500),
+                true_peak: true,
+            },
+        }
+    }
+}
+
+/// Levels of one channel, all linear with 1.0 being full scale.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelLevel {
+    pub position: Position,
+    /// Highest absolute sample value in the last block.
+    pub sample_peak: f32,
+    /// Highest true peak in the last block.
+    pub true_peak: f32,
+    /// Highest true peak since the meter was created.
+    pub max_true_peak: f32,
+    /// Root mean square over the last 300 ms.
+    pub rms: f32,
+    /// The reading of a meter with the configured ballistics.
+    pub reading: f32,
+}
+
+/// Per-channel state that's not part of the reported levels.
+#[derive(Debug, Clone, Default)]
+struct ChannelState {
+    true_peak: TruePeak,
+    mean_square: f32,
+    /// Samples left until a held peak starts falling.
+    hold_left: usize,
+}
+
+/// Peak, RMS, true peak and ballistic levels of every channel.
+#[derive(Debug, Clone)]
+pub struct LevelMeter {
+    ballistics: Ballistics,
+    rate: u32,
+    levels: Vec<ChannelLevel>,
+    states: Vec<ChannelState>,
+}
+
+impl LevelMeter {
+    pub fn new(ballistics: Ballistics) -> Self {
+        LevelMeter {
+            ballistics,
+            rate: 0,
+            levels: Vec::new(),
+            states: Vec::new(),
+        }
+    }
+
+    /// The levels after the last block, one per channel.
+    pub fn levels(&self) -> &[ChannelLevel] {
+        &self.levels
+    }
+
+    /// Measures `block`. A block with a different rate or channel layout
+    /// resets the meter.
+    pub fn push(&mut self, block: &AudioBlock) {
+        let layout_changed = self.levels.len() != block.positions.len()
+            || self
+                .levels
+                .iter()
+                .zip(&block.positions)
+                .any(|(level, &position)| level.position != position);
+        if block.rate != self.rate || layout_changed {
+            self.rate = block.rate;
+            self.levels = block
+                .positions
+                .iter()
+                .map(|&position| ChannelLevel {
+                    position,
+                    sample_peak: 0.0,
+                    true_peak: 0.0,
+                    max_true_peak: 0.0,
+                    rms: 0.0,
+                    reading: 0.0,
+                })
+                .collect();
+            self.states = vec![ChannelState::default(); block.channels.len()];
+        }
+
+        let rate = self.rate as f32;
+        let rms_keep = smoothing(RMS_INTEGRATION, rate);
+        let attack_keep = smoothing(self.ballistics.attack, rate);
+        let release_factor = match self.ballistics.release {
+            Release::TimeConstant(time) => smoothing(time, rate),
+            Release::DbPerSecond(db) => 10f32.powf(-db / 20.0 / rate),
+        };
+        let hold = (self.ballistics.hold.as_secs_f32() * rate) as usize;
+
+        for ((level, state), samples) in self
+            .levels
+            .iter_mut()
+            .zip(&mut self.states)
+            .zip(&block.channels)
+        {
+            level.sample_peak = 0.0;
+            level.true_peak = 0.0;
+
+            for &sample in samples {
+                let true_peak = state.true_peak.process(sample);
+                level.sample_peak = level.sample_peak.max(sample.abs());
+                level.true_peak = level.true_peak.max(true_peak);
+                state.mean_square =
+                    rms_keep * state.mean_square + (1.0 - rms_keep) * sample * sample;
+
+                let input = if self.ballistics.true_peak {
+                    true_peak
+                } else {
+                    sample.abs()
+                };
+                if input >= level.reading {
+                    level.reading = attack_keep * level.reading + (1.0 - attack_keep) * input;
+                    state.hold_left = hold;
+                } else if state.hold_left > 0 {
+                    state.hold_left -= 1;
+                } else {
+                    level.reading = match self.ballistics.release {
+                        // Averaging meters fall towards the signal, not to
+                        // silence.
+                        Release::TimeConstant(_) => {
+                            release_factor * level.reading + (1.0 - release_factor) * input
+                        }
+                        Release::DbPerSecond(_) => (level.reading * release_factor).max(input),
+                    };
+                }
+            }
+
+            level.max_true_peak = level.max_true_peak.max(level.true_peak);
+            level.rms = state.mean_square.sqrt();
+        }
+    }
+}
+
+/// The factor a one pole smoother keeps of its previous value per sample,
+/// for time constant `time` at `rate` Hz. Zero for instant response.
+fn smoothing(time: Duration, rate: f32) -> f32 {
+    let samples = time.as_secs_f32() * rate;
+    if samples <= 0.0 {
+        return 0.0;
+    }
+
+    (-1.0 / samples).exp()
+}
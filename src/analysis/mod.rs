@@ -0,0 +1,34 @@
+//! Measurements computed from decoded audio.
+
+use bands::BandConfig;
+use meter::Ballistics;
+use pulse::channelmap::Position;
+use spectrum::SpectrumConfig;
+
+pub mod bands;
+pub mod loudness;
+pub mod meter;
+pub mod spectrum;
+pub mod stereo;
+pub mod waveform;
+
+/// Settings of the analyses the views are built on.
+#[derive(Debug, Clone, Copy)]
+pub struct AnalysisConfig {
+    pub spectrum: SpectrumConfig,
+    pub bands: BandConfig,
+    pub ballistics: Ballistics,
+    /// Integrated loudness the loudness summary compares against, in LUFS.
+    pub loudness_target: f64,
+}
+
+/// Indices of the left and right channel: the front left and right ones if
+/// the map has them, the first two channels otherwise. `None` for mono.
+pub fn stereo_pair(positions: &[Position]) -> Option<(usize, usize)> {
+    let find = |position| positions.iter().position(|&p| p == position);
+    match (find(Position::FrontLeft), find(Position::FrontRight)) {
+        (Some(left), Some(right)) => Some((left, right)),
+        _ if positions.len() >= 2 => Some((0, 1)),
+        _ => None,
+    }
+}
@@ -0,0 +1,211 @@
+use std::{f32::consts::PI, sync::Arc};
+
+use clap::ValueEnum;
+use pulse::channelmap::Position;
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+
+use crate::decode::AudioBlock;
+
+/// Window applied to each FFT frame before transforming it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Window {
+    Hann,
+    Hamming,
+    BlackmanHarris,
+    FlatTop,
+}
+
+impl Window {
+    /// Coefficients of the window as a sum of cosines.
+    fn cosine_terms(self) -> &'static [f32] {
+        match self {
+            Window::Hann => &[0.5, 0.5],
+            Window::Hamming => &[0.54, 0.46],
+            Window::BlackmanHarris => &[0.35875, 0.48829, 0.14128, 0.01168],
+            Window::FlatTop => &[
+                0.215_578_95,
+                0.416_631_58,
+                0.277_263_16,
+                0.083_578_95,
+                0.006_947_368,
+            ],
+        }
+    }
+
+    /// The periodic window of length `size`.
+    pub fn coefficients(self, size: usize) -> Vec<f32> {
+        let terms = self.cosine_terms();
+        (0..size)
+            .map(|n| {
+                let phase = 2.0 * PI * n as f32 / size as f32;
+                terms
+                    .iter()
+                    .enumerate()
+                    .map(|(k, a)| {
+                        let sign = if k % 2 == 0 { 1.0 } else { -1.0 };
+                        sign * a * (k as f32 * phase).cos()
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpectrumConfig {
+    pub fft_size: usize,
+    /// Samples between the starts of consecutive frames. Smaller than
+    /// `fft_size` means frames overlap.
+    pub hop_size: usize,
+    pub window: Window,
+    /// Lowest level reported, in dBFS.
+    pub db_floor: f32,
+}
+
+impl SpectrumConfig {
+    /// A hop size that makes consecutive frames overlap by `overlap`, a
+    /// fraction in 0.0..1.0.
+    pub fn hop_for_overlap(fft_size: usize, overlap: f32) -> usize {
+        ((fft_size as f32 * (1.0 - overlap.clamp(0.0, 0.99))).round() as usize).max(1)
+    }
+}
+
+impl Default for SpectrumConfig {
+    fn default() -> Self {
+        SpectrumConfig {
+            fft_size: 2048,
+            hop_size: 1024,
+            window: Window::Hann,
+            db_floor: -120.0,
+        }
+    }
+}
+
+/// Magnitudes of one FFT frame, from DC up to the Nyquist frequency.
+///
+/// Magnitudes are normalized by the window's gain, so a full scale sine
+/// centered on a bin reads 1.0 (0 dBFS).
+#[derive(Debug, Clone)]
+pub struct Spectrum {
+    pub rate: u32,
+    pub fft_size: usize,
+    pub magnitudes: Vec<f32>,
+}
+
+/// Converts a linear amplitude to dBFS, clamped to `floor`.
+pub fn to_db(amplitude: f32, floor: f32) -> f32 {
+    if amplitude <= 0.0 {
+        return floor;
+    }
+
+    (20.0 * amplitude.log10()).max(floor)
+}
+
+/// Turns a stream of [`AudioBlock`]s into a stream of [`Spectrum`]s, one
+/// every `hop_size` samples. Channels are mixed down to mono first, leaving
+/// out the LFE channel, which only duplicates the low end.
+pub struct SpectrumAnalyzer {
+    config: SpectrumConfig,
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    window_gain: f32,
+    rate: u32,
+    samples: Vec<f32>,
+    /// Samples still to be skipped before the next frame starts, when the
+    /// hop is larger than a frame.
+    skip: usize,
+    buffer: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+}
+
+impl SpectrumAnalyzer {
+    pub fn new(config: SpectrumConfig) -> Self {
+        let fft = FftPlanner::new().plan_fft_forward(config.fft_size);
+        let window = config.window.coefficients(config.fft_size);
+        let window_gain = window.iter().sum();
+        let scratch = vec![Complex::default(); fft.get_inplace_scratch_len()];
+
+        SpectrumAnalyzer {
+            config,
+            fft,
+            window,
+            window_gain,
+            rate: 0,
+            samples: Vec::with_capacity(config.fft_size * 2),
+            skip: 0,
+            buffer: vec![Complex::default(); config.fft_size],
+            scratch,
+        }
+    }
+
+    /// Adds `block` and calls `on_spectrum` for every frame that became
+    /// complete.
+    pub fn push(&mut self, block: &AudioBlock, mut on_spectrum: impl FnMut(&Spectrum)) {
+        if block.rate != self.rate {
+            self.rate = block.rate;
+            self.samples.clear();
+            self.skip = 0;
+        }
+
+        let mixed: Vec<bool> = block
+            .positions
+            .iter()
+            .map(|&position| position != Position::Lfe)
+            .collect();
+        let channels = mixed.iter().filter(|&&mixed| mixed).count().max(1) as f32;
+
+        let mut samples = std::mem::take(&mut self.samples);
+        samples.extend(block.frames().map(|frame| {
+            frame
+                .samples()
+                .zip(&mixed)
+                .filter(|(_, &mixed)| mixed)
+                .map(|(sample, _)| sample)
+                .sum::<f32>()
+                / channels
+        }));
+
+        let mut start = self.skip;
+        while start + self.config.fft_size <= samples.len() {
+            let spectrum = self.transform(&samples[start..start + self.config.fft_size]);
+            on_spectrum(&spectrum);
+            start += self.config.hop_size;
+        }
+
+        let consumed = start.min(samples.len());
+        self.skip = start - consumed;
+        samples.drain(..consumed);
+        self.samples = samples;
+    }
+
+    fn transform(&mut self, samples: &[f32]) -> Spectrum {
+        for ((bin, &sample), &weight) in self.buffer.iter_mut().zip(samples).zip(&self.window) {
+            *bin = Complex::new(sample * weight, 0.0);
+        }
+
+        self.fft
+            .process_with_scratch(&mut self.buffer, &mut self.scratch);
+
+        let bins = self.config.fft_size / 2 + 1;
+        let magnitudes = self.buffer[..bins]
+            .iter()
+            .enumerate()
+            .map(|(index, bin)| {
+                // Every bin but DC and Nyquist has a mirror image holding the
+                // other half of its energy.
+                let scale = if index == 0 || index * 2 == self.config.fft_size {
+                    1.0
+                } else {
+                    2.0
+                };
+                bin.norm() * scale / self.window_gain
+            })
+            .collect();
+
+        Spectrum {
+            rate: self.rate,
+            fft_size: self.config.fft_size,
+            magnitudes,
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+use crate::{analysis::stereo_pair, decode::AudioBlock};
+
+/// Power below which the channels count as silent and the correlation reads
+/// 0.0, about -100 dBFS.
+const SILENCE: f32 = 1e-10;
+
+/// Mid and side of a stereo frame, `(left + right) / 2` and
+/// `(left - right) / 2`.
+///
+/// Plotting side against mid is the 45° rotation a goniometer shows: mono
+/// is a vertical line, left only leans left, right only leans right and
+/// out of phase content is horizontal.
+pub fn mid_side(left: f32, right: f32) -> (f32, f32) {
+    ((left + right) / 2.0, (left - right) / 2.0)
+}
+
+/// Phase correlation of the left and right channel, from -1.0 (out of
+/// phase) through 0.0 (unrelated) to +1.0 (mono).
+///
+/// Products and powers are averaged exponentially over the integration
+/// time, like the needle of a hardware correlation meter.
+#[derive(Debug, Clone)]
+pub struct CorrelationMeter {
+    integration: Duration,
+    rate: u32,
+    coefficient: f32,
+    product: f32,
+    left_power: f32,
+    right_power: f32,
+}
+
+impl CorrelationMeter {
+    pub fn new(integration: Duration) -> Self {
+        CorrelationMeter {
+            integration,
+            rate: 0,
+            coefficient: 0.0,
+            product: 0.0,
+            left_power: 0.0,
+            right_power: 0.0,
+        }
+    }
+
+    /// Adds the stereo pair of `block`. Mono blocks count as fully
+    /// correlated.
+    pub fn push(&mut self, block: &AudioBlock) {
+        if block.rate != self.rate {
+            self.rate = block.rate;
+            let samples = self.integration.as_secs_f32() * block.rate as f32;
+            self.coefficient = (-1.0 / samples.max(1.0)).exp();
+        }
+
+        let (left, right) = match stereo_pair(&block.positions) {
+            Some((left, right)) => (&block.channels[left], &block.channels[right]),
+            None => match block.channels.first() {
+                Some(mono) => (mono, mono),
+                None => return,
+            },
+        };
+
+        let keep = self.coefficient;
+        for (&left, &right) in left.iter().zip(right) {
+            self.product = keep * self.product + (1.0 - keep) * left * right;
+            self.left_power = keep * self.left_power + (1.0 - keep) * left * left;
+            self.right_power = keep * self.right_power + (1.0 - keep) * right * right;
+        }
+    }
+
+    /// The current correlation, 0.0 while either channel is silent.
+    pub fn value(&self) -> f32 {
+        let power = self.left_power * self.right_power;
+        if power < SILENCE * SILENCE {
+            return 0.0;
+        }
+
+        (self.product / power.sqrt()).clamp(-1.0, 1.0)
+    }
+}
@@ -0,0 +1,110 @@
+use std::time::Duration;
+
+use pulse::channelmap::Position;
+
+use crate::decode::AudioBlock;
+
+/// Level a trigger signal has to rise above after being at or below zero,
+/// so noise around zero doesn't trigger.
+const TRIGGER_HYSTERESIS: f32 = 0.01;
+
+/// The most recent samples of every channel, for drawing waveforms.
+///
+/// Keeps twice the requested window, so there is always room to look back
+/// for a trigger point and still show a whole window after it.
+#[derive(Debug, Clone)]
+pub struct WaveformBuffer {
+    duration: Duration,
+    window: usize,
+    rate: u32,
+    positions: Vec<Position>,
+    channels: Vec<Vec<f32>>,
+}
+
+impl WaveformBuffer {
+    /// A buffer showing `duration` of audio at a time.
+    pub fn new(duration: Duration) -> Self {
+        WaveformBuffer {
+            duration,
+            window: 0,
+            rate: 0,
+            positions: Vec::new(),
+            channels: Vec::new(),
+        }
+    }
+
+    pub fn positions(&self) -> &[Position] {
+        &self.positions
+    }
+
+    /// Appends `block`, dropping everything but the last two windows. A
+    /// block with a different rate or channel layout starts over.
+    pub fn push(&mut self, block: &AudioBlock) {
+        if block.rate != self.rate || block.positions != self.positions {
+            self.rate = block.rate;
+            self.window = ((self.duration.as_secs_f64() * block.rate as f64) as usize).max(2);
+            self.positions = block.positions.clone();
+            self.channels = vec![Vec::with_capacity(self.window * 2); block.channels.len()];
+        }
+
+        for (history, samples) in self.channels.iter_mut().zip(&block.channels) {
+            history.extend_from_slice(samples);
+            let excess = history.len().saturating_sub(self.window * 2);
+            history.drain(..excess);
+        }
+    }
+
+    /// The latest window of samples per channel, or less until enough
+    /// audio came in.
+    pub fn latest(&self) -> Vec<&[f32]> {
+        self.channels
+            .iter()
+            .map(|samples| &samples[samples.len().saturating_sub(self.window)..])
+            .collect()
+    }
+
+    /// One window of samples per channel, starting at the latest rising
+    /// zero crossing of `trigger_channel` that still leaves a whole window
+    /// after it. Without such a crossing the latest window is returned, so
+    /// the trace free-runs. Shorter than a window until enough audio came
+    /// in.
+    pub fn triggered(&self, trigger_channel: usize) -> Vec<&[f32]> {
+        let available = self.channels.first().map_or(0, Vec::len);
+        let len = self.window.min(available);
+        let latest = available - len;
+
+        let start = self
+            .channels
+            .get(trigger_channel)
+            .filter(|_| len > 0)
+            .and_then(|trigger| rising_zero_crossing(&trigger[..=latest]))
+            .unwrap_or(latest);
+
+        self.channels
+            .iter()
+            .map(|samples| &samples[start..start + len])
+            .collect()
+    }
+}
+
+/// The index of the first positive sample of the last rise from zero or
+/// below to above [`TRIGGER_HYSTERESIS`].
+fn rising_zero_crossing(samples: &[f32]) -> Option<usize> {
+    let mut below = false;
+    let mut rise = None;
+    let mut crossing = None;
+    for (index, &sample) in samples.iter().enumerate() {
+        if sample <= 0.0 {
+            below = true;
+            rise = None;
+        } else if below {
+            let start = *rise.get_or_insert(index);
+            if sample > TRIGGER_HYSTERESIS {
+                below = false;
+                crossing = Some(start);
+            }
+        }
+    }
+
+    crossing
+}
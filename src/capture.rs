@@ -0,0 +1,450 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use pulse::{channelmap::Map, sample::Spec};
+
+use crate::{
+    device::{self, DeviceSelector},
+    error::Error,
+    logging,
+    server::{Connector, Peek, PulseConnector, RecordStream, Server, StreamState},
+    source::{AudioSource, SourceConfig},
+    spec::SpecRequest,
+};
+
+/// How long one mainloop iteration may block before the capture thread checks
+/// whether it was asked to stop.
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How the capture thread recovers from losing the server or the device.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReconnectPolicy {
+    /// Whether to reconnect at all. Errors before the stream was ready the
+    /// first time are always fatal, so a bad device selector isn't retried
+    /// forever.
+    pub enabled: bool,
+    /// Delay before the first attempt, doubled after each failed attempt.
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            enabled: true,
+            initial_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Exponential backoff between reconnect attempts.
+struct Backoff {
+    policy: ReconnectPolicy,
+    attempt: u32,
+}
+
+impl Backoff {
+    fn new(policy: ReconnectPolicy) -> Self {
+        Backoff { policy, attempt: 0 }
+    }
+
+    /// Returns the attempt number and how long to wait before it.
+    fn next(&mut self) -> (u32, Duration) {
+        let delay = self
+            .policy
+            .initial_delay
+            .saturating_mul(1 << self.attempt.min(16))
+            .min(self.policy.max_delay);
+        self.attempt += 1;
+        (self.attempt, delay)
+    }
+
+    fn reset(&mut self) {
+        self.attempt = 0;
+    }
+}
+
+/// What to record and how to keep recording.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptureConfig {
+    pub selector: DeviceSelector,
+    /// Resolved against the device's native spec on every (re)connect.
+    pub spec: SpecRequest,
+    pub reconnect: ReconnectPolicy,
+    /// Move the stream to the monitor of the new default sink whenever the
+    /// default sink changes. Reconnects then also resolve the default sink
+    /// instead of `selector`.
+    pub follow_default: bool,
+}
+
+/// What the capture thread reports to whoever consumes the record stream.
+#[derive(Debug)]
+pub enum CaptureEvent {
+    /// The record stream is connected and delivering audio in `spec`. Sent
+    /// again after every successful reconnect.
+    Ready {
+        sink: String,
+        source: String,
+        spec: Spec,
+        channel_map: Map,
+    },
+    /// The default sink changed and the stream moved to its monitor.
+    Switched { sink: String, source: String },
+    /// A new configuration named no device or an impossible spec, so
+    /// recording carries on as configured before.
+    Rejected(Error),
+    /// The stream or server was lost; capture retries after `delay`.
+    Reconnecting {
+        error: Error,
+        attempt: u32,
+        delay: Duration,
+    },
+    /// Raw bytes in the stream's sample spec.
+    Data(Vec<u8>),
+    /// The server dropped this many bytes.
+    Hole(usize),
+    /// Capture stopped because of an error and won't recover.
+    Failed(Error),
+}
+
+/// A record stream on a sink's monitor source, running on its own thread.
+///
+/// The thread owns the server connection and the stream. It only iterates
+/// the mainloop blocking, so an idle capture uses next to no CPU. Audio is
+/// read after every iteration and forwarded over a channel.
+///
+/// When the server restarts or the device disappears, the thread recreates
+/// the context and stream according to its [`ReconnectPolicy`], resolving
+/// the device selector again each time. A new configuration only recreates
+/// the stream, on the same context.
+pub struct Capture {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+    events: Receiver<CaptureEvent>,
+    configs: Sender<CaptureConfig>,
+}
+
+impl Capture {
+    /// Captures from the default PulseAudio server.
+    pub fn start(config: CaptureConfig) -> Capture {
+        Capture::start_with(PulseConnector, config)
+    }
+
+    /// Captures from the servers `connector` connects to.
+    pub fn start_with<C: Connector>(connector: C, config: CaptureConfig) -> Capture {
+        let (tx, rx) = mpsc::channel();
+        let (configs, new_configs) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let stop = stop.clone();
+            thread::Builder::new()
+                .name("capture".to_string())
+                .spawn(move || supervise(&connector, config, &new_configs, &tx, &stop))
+                .unwrap()
+        };
+
+        Capture {
+            stop,
+            thread: Some(thread),
+            events: rx,
+            configs,
+        }
+    }
+
+    /// Records according to `config` from now on. The stream is recreated
+    /// on the current connection, followed by a new `Ready`, unless the
+    /// configuration didn't change. If its device or spec can't be resolved,
+    /// the current stream keeps going and `Rejected` is sent instead.
+    pub fn reconfigure(&self, config: CaptureConfig) {
+        let _ = self.configs.send(config);
+    }
+}
+
+impl AudioSource for Capture {
+    fn recv_timeout(&mut self, timeout: Duration) -> Result<CaptureEvent, RecvTimeoutError> {
+        self.events.recv_timeout(timeout)
+    }
+
+    fn reconfigure(&mut self, config: &SourceConfig) -> bool {
+        match config {
+            SourceConfig::Capture(config) => {
+                Capture::reconfigure(self, (**config).clone());
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Drop for Capture {
+    /// Disconnects the stream and waits for the capture thread to finish.
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Runs the capture until it's stopped, reconnecting with backoff when it
+/// fails after having been ready.
+fn supervise(
+    connector: &impl Connector,
+    mut config: CaptureConfig,
+    configs: &Receiver<CaptureConfig>,
+    tx: &Sender<CaptureEvent>,
+    stop: &AtomicBool,
+) {
+    let mut backoff = Backoff::new(config.reconnect);
+    let mut was_ready = false;
+
+    loop {
+        let selector = if config.follow_default && was_ready {
+            DeviceSelector::DefaultSink
+        } else {
+            config.selector.clone()
+        };
+
+        let mut ready = false;
+        let result = run(
+            connector,
+            &mut config,
+            selector,
+            configs,
+            tx,
+            stop,
+            &mut ready,
+        );
+        was_ready |= ready;
+        if ready {
+            backoff.reset();
+        }
+
+        let policy = config.reconnect;
+        let error = match result {
+            Ok(()) => return,
+            Err(error) if !policy.enabled || !was_ready => {
+                log::debug!(target: logging::STREAM, "Giving up: {}", error);
+                let _ = tx.send(CaptureEvent::Failed(error));
+                return;
+            }
+            Err(error) => error,
+        };
+
+        let (attempt, delay) = backoff.next();
+        log::warn!(
+            target: logging::STREAM,
+            "{} Reconnecting in {:?} (attempt {}).",
+            error,
+            delay,
+            attempt
+        );
+        let sent = tx.send(CaptureEvent::Reconnecting {
+            error,
+            attempt,
+            delay,
+        });
+        if sent.is_err() || !sleep_unless_stopped(delay, stop) {
+            return;
+        }
+    }
+}
+
+/// Sleeps for `duration`, waking up early if capture is stopped. Returns
+/// false if it was.
+fn sleep_unless_stopped(duration: Duration, stop: &AtomicBool) -> bool {
+    let deadline = Instant::now() + duration;
+    while !stop.load(Ordering::Relaxed) {
+        let Some(remaining) = deadline.checked_duration_since(Instant::now()) else {
+            return true;
+        };
+        thread::sleep(remaining.min(STOP_POLL_INTERVAL));
+    }
+
+    false
+}
+
+/// Connects and records until stopped or until something fails, starting
+/// a new stream whenever `configs` delivers a different configuration.
+/// `ready` is set once a stream delivered its first `Ready`.
+fn run(
+    connector: &impl Connector,
+    config: &mut CaptureConfig,
+    selector: DeviceSelector,
+    configs: &Receiver<CaptureConfig>,
+    tx: &Sender<CaptureEvent>,
+    stop: &AtomicBool,
+    ready: &mut bool,
+) -> Result<(), Error> {
+    let server = connector.connect()?;
+
+    let mut target = resolve(&server, &selector, &config.spec)?;
+    while let Some((new_config, new_target)) =
+        record(&server, config, target, configs, tx, stop, ready)?
+    {
+        *config = new_config;
+        target = new_target;
+    }
+    server.disconnect();
+
+    Ok(())
+}
+
+/// What a record stream is started on.
+struct Target {
+    sink: String,
+    source: String,
+    spec: Spec,
+    channel_map: Map,
+}
+
+/// Resolves the device `selector` picks and the spec to record it in.
+fn resolve<S: Server>(
+    server: &S,
+    selector: &DeviceSelector,
+    spec: &SpecRequest,
+) -> Result<Target, Error> {
+    let sinks = server.sinks()?;
+    let defaults = server.server_defaults()?;
+    let (sink, source) = device::resolve_monitor(selector, &sinks, defaults.sink.as_deref())?;
+    let (spec, channel_map) = spec.resolve(&sink.native_spec, &sink.native_channel_map)?;
+    Ok(Target {
+        sink: sink.name.clone(),
+        source,
+        spec,
+        channel_map,
+    })
+}
+
+/// Records from the monitor of `target` until stopped, or until a
+/// configuration different from `config` arrives whose device and spec
+/// resolve, which is then returned with them. The current stream is only
+/// disconnected once they did.
+fn record<S: Server>(
+    server: &S,
+    config: &CaptureConfig,
+    target: Target,
+    configs: &Receiver<CaptureConfig>,
+    tx: &Sender<CaptureEvent>,
+    stop: &AtomicBool,
+    ready: &mut bool,
+) -> Result<Option<(CaptureConfig, Target)>, Error> {
+    let Target {
+        sink,
+        source,
+        spec,
+        channel_map,
+    } = target;
+
+    let mut stream = server.record(&source, &spec, &channel_map)?;
+    if config.follow_default {
+        server.watch_default_sink();
+    }
+
+    let mut current_sink = sink.clone();
+    let mut delivering = false;
+    let mut new_config = None;
+    while !stop.load(Ordering::Relaxed) {
+        // Only the latest of several quick changes matters.
+        if let Some(latest) = configs.try_iter().last() {
+            if latest != *config {
+                match resolve(server, &latest.selector, &latest.spec) {
+                    Ok(target) => {
+                        log::info!(target: logging::STREAM, "Configuration changed, restarting the stream.");
+                        new_config = Some((latest, target));
+                        break;
+                    }
+                    Err(err @ (Error::NoDevice(_) | Error::InvalidSpec(_))) => {
+                        log::warn!(target: logging::STREAM, "{} Keeping the current stream.", err);
+                        let _ = tx.send(CaptureEvent::Rejected(err));
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+
+        server.iterate(STOP_POLL_INTERVAL)?;
+
+        match stream.state() {
+            StreamState::Connecting => continue,
+            StreamState::Failed(err) => return Err(Error::Stream(err)),
+            StreamState::Ready => {}
+        }
+
+        if !delivering {
+            log::info!(target: logging::STREAM, "Recording from {}.", source);
+            delivering = true;
+            *ready = true;
+            let _ = tx.send(CaptureEvent::Ready {
+                sink: sink.clone(),
+                source: source.clone(),
+                spec,
+                channel_map,
+            });
+        }
+
+        // Drains everything readable, so no fragment is delivered twice.
+        loop {
+            let event = match stream.read()? {
+                Peek::Empty => break,
+                Peek::Hole(len) => CaptureEvent::Hole(len),
+                Peek::Data(data) => CaptureEvent::Data(data),
+            };
+            let _ = tx.send(event);
+        }
+
+        if server.default_sink_changed() {
+            if let Some((sink, source)) = follow_default(server, &stream, &current_sink)? {
+                log::info!(target: logging::STREAM, "Default sink changed, now recording {}.", source);
+                current_sink = sink.clone();
+                let _ = tx.send(CaptureEvent::Switched { sink, source });
+            }
+        }
+    }
+
+    if let Err(err) = stream.disconnect() {
+        log::warn!(target: logging::STREAM, "Error disconnecting stream: {}", err);
+    }
+
+    Ok(new_config)
+}
+
+/// Moves `stream` to the monitor of the default sink if that isn't
+/// `current_sink` anymore. Returns the new sink and source names if it did.
+fn follow_default<S: Server>(
+    server: &S,
+    stream: &S::Stream,
+    current_sink: &str,
+) -> Result<Option<(String, String)>, Error> {
+    let defaults = server.server_defaults()?;
+    if defaults.sink.as_deref() == Some(current_sink) {
+        return Ok(None);
+    }
+
+    // The default can briefly point at a sink that's already gone while
+    // outputs are switched; the next event resolves it.
+    let sinks = server.sinks()?;
+    let Ok((sink, source)) = device::resolve_monitor(
+        &DeviceSelector::DefaultSink,
+        &sinks,
+        defaults.sink.as_deref(),
+    ) else {
+        return Ok(None);
+    };
+
+    let Some(index) = stream.index() else {
+        return Ok(None);
+    };
+    server.move_source_output(index, &source)?;
+
+    Ok(Some((sink.name.clone(), source)))
+}
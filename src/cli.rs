@@ -0,0 +1,560 @@
+use std::{path::PathBuf, str::FromStr, time::Duration};
+
+use clap::{Args as ClapArgs, Parser, Subcommand};
+use pulse::{channelmap::Map, sample::Format};
+
+use pulse_visualizer::{
+    analysis::{
+        bands::{BandConfig, BandScale},
+        meter::{Ballistics, BallisticsPreset, Release},
+        spectrum::{SpectrumConfig, Window},
+        AnalysisConfig,
+    },
+    capture::{CaptureConfig, ReconnectPolicy},
+    device::DeviceSelector,
+    generator::{GeneratorConfig, Signal},
+    logging::{LogConfig, LogFilter},
+    reload::Settings,
+    render::{
+        color::{ColorMode, Colormap},
+        scope::ScopeLayout,
+        spectrogram::SpectrogramConfig,
+        DisplayConfig, View,
+    },
+    source::SourceConfig,
+    spec::{self, SpecRequest},
+    stop::StopConfig,
+};
+
+/// Visualizes the audio playing on a PulseAudio sink.
+#[derive(Parser)]
+pub struct Args {
+    #[command(flatten)]
+    pub config: ConfigArgs,
+
+    #[command(flatten)]
+    pub capture: CaptureArgs,
+
+    #[command(flatten)]
+    pub spectrum: SpectrumArgs,
+
+    #[command(flatten)]
+    pub bands: BandArgs,
+
+    #[command(flatten)]
+    pub meter: MeterArgs,
+
+    #[command(flatten)]
+    pub loudness: LoudnessArgs,
+
+    #[command(flatten)]
+    pub display: DisplayArgs,
+
+    #[command(flatten)]
+    pub stop: StopArgs,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Args {
+    /// The settings the visualizer starts with, and reloads.
+    pub fn settings(&self) -> Settings {
+        Settings {
+            source: self.capture.to_source(),
+            analysis: AnalysisConfig {
+                spectrum: self.spectrum.to_config(),
+                bands: self.bands.to_config(),
+                ballistics: self.meter.to_ballistics(),
+                loudness_target: self.loudness.loudness_target,
+            },
+            display: self.display.to_config(),
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// List sinks and sources with their sample specs and channel maps.
+    ListDevices {
+        /// Print the list as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Record the monitored sink to WAV files until interrupted.
+    Record {
+        /// File to write. With --max-duration, files are numbered like
+        /// `name-001.wav` instead.
+        output: PathBuf,
+
+        /// Start a new file after this many seconds.
+        #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+        max_duration: Option<u64>,
+    },
+}
+
+/// Which settings to read from the configuration file.
+#[derive(ClapArgs)]
+pub struct ConfigArgs {
+    /// Configuration file to read instead of
+    /// `$XDG_CONFIG_HOME/pulse-visualizer/config.toml`. Options given on
+    /// the command line override it. While visualizing, changes to the
+    /// file are applied right away.
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Apply the settings of this `[profiles.<name>]` table of the
+    /// configuration file.
+    #[arg(long)]
+    pub profile: Option<String>,
+
+    /// Ignore the configuration file.
+    #[arg(long, conflicts_with_all = ["config", "profile"])]
+    pub no_config: bool,
+}
+
+/// Where to record from and in which format.
+#[derive(ClapArgs)]
+pub struct CaptureArgs {
+    /// Sink to monitor: a name, a unique name substring, an index, a
+    /// `/regex/`, or `@DEFAULT_SINK@`/`@DEFAULT_MONITOR@`.
+    #[arg(long, short, default_value = "@DEFAULT_MONITOR@")]
+    device: DeviceSelector,
+
+    /// Exit when the server or device goes away instead of reconnecting.
+    #[arg(long)]
+    no_reconnect: bool,
+
+    /// Switch to the new default sink's monitor whenever the default sink
+    /// changes.
+    #[arg(long)]
+    follow_default: bool,
+
+    /// Sample format to record in: u8, s16le, s24le, s32le or float32le.
+    /// Defaults to the device's format.
+    #[arg(long, value_parser = spec::parse_format)]
+    format: Option<Format>,
+
+    /// Sample rate in Hz. Defaults to the device's rate.
+    #[arg(long)]
+    rate: Option<u32>,
+
+    /// Number of channels. Defaults to the channel map's or the device's.
+    #[arg(long)]
+    channels: Option<u8>,
+
+    /// Channel map, e.g. `stereo`, `surround-51` or
+    /// `front-left,front-right`. Defaults to the device's.
+    #[arg(long, value_parser = spec::parse_channel_map)]
+    channel_map: Option<Map>,
+
+    /// Analyze a WAV file instead of recording a sink. It is played in real
+    /// time unless --fast is given.
+    #[arg(long, short, conflicts_with_all = ["device", "follow_default"])]
+    input: Option<PathBuf>,
+
+    /// Read the --input file as fast as possible instead of in real time.
+    #[arg(long, requires = "input")]
+    fast: bool,
+
+    #[command(flatten)]
+    generator: GeneratorArgs,
+}
+
+impl CaptureArgs {
+    /// The file given with --input, the signal given with --generate, or
+    /// the sink to record otherwise.
+    pub fn to_source(&self) -> SourceConfig {
+        if let Some(path) = &self.input {
+            return SourceConfig::File {
+                path: path.clone(),
+                paced: !self.fast,
+            };
+        }
+
+        match self.generator.generate {
+            Some(signal) => {
+                SourceConfig::Generator(Box::new(self.generator.to_config(signal, self.spec())))
+            }
+            None => SourceConfig::Capture(Box::new(self.to_config())),
+        }
+    }
+
+    fn spec(&self) -> SpecRequest {
+        SpecRequest {
+            format: self.format,
+            rate: self.rate,
+            channels: self.channels,
+            channel_map: self.channel_map,
+        }
+    }
+
+    fn to_config(&self) -> CaptureConfig {
+        CaptureConfig {
+            selector: self.device.clone(),
+            spec: self.spec(),
+            reconnect: ReconnectPolicy {
+                enabled: !self.no_reconnect,
+                ..ReconnectPolicy::default()
+            },
+            follow_default: self.follow_default,
+        }
+    }
+}
+
+/// A test signal to analyze instead of a sink.
+#[derive(ClapArgs)]
+pub struct GeneratorArgs {
+    /// Generate a test signal instead of recording a sink.
+    #[arg(long, conflicts_with_all = ["device", "follow_default", "input"])]
+    generate: Option<Signal>,
+
+    /// Frequency of the test signal, where sweeps start, or how many
+    /// impulses to generate per second, in Hz. Below half the sample rate.
+    #[arg(long, default_value_t = 1000.0, value_parser = parse_frequency::<f64>)]
+    frequency: f64,
+
+    /// Frequency sweeps end at, in Hz. Below half the sample rate.
+    #[arg(long, default_value_t = 20000.0, value_parser = parse_frequency::<f64>)]
+    end_frequency: f64,
+
+    /// Length of one sweep, in seconds.
+    #[arg(long, default_value = "10", value_parser = parse_seconds)]
+    sweep_duration: Duration,
+
+    /// Comma separated frequencies of the multi-tone signal, in Hz. Below
+    /// half the sample rate.
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "100,1000,10000",
+        value_parser = parse_frequency::<f64>
+    )]
+    tones: Vec<f64>,
+
+    /// Peak level of the test signal, in dBFS.
+    #[arg(long, default_value_t = -20.0, allow_negative_numbers = true)]
+    amplitude: f32,
+
+    /// Channels that carry the test signal, e.g. `front-left`. The others
+    /// are silent. Defaults to all channels.
+    #[arg(long, value_parser = spec::parse_channel_map)]
+    route: Option<Map>,
+}
+
+impl GeneratorArgs {
+    fn to_config(&self, signal: Signal, spec: SpecRequest) -> GeneratorConfig {
+        GeneratorConfig {
+            signal,
+            frequency: self.frequency,
+            end_frequency: self.end_frequency,
+            sweep_duration: self.sweep_duration,
+            tones: self.tones.clone(),
+            amplitude: self.amplitude,
+            route: self.route.map_or(Vec::new(), |map| map.get().to_vec()),
+            spec,
+        }
+    }
+}
+
+/// How audio is turned into spectra.
+#[derive(ClapArgs)]
+pub struct SpectrumArgs {
+    /// Samples per FFT frame.
+    #[arg(long, default_value_t = 2048, value_parser = clap::value_parser!(u32).range(16..=65536))]
+    fft_size: u32,
+
+    /// Samples between the starts of consecutive FFT frames, at most the FFT
+    /// size. Overrides --overlap.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    hop_size: Option<u32>,
+
+    /// Fraction by which consecutive FFT frames overlap.
+    #[arg(long, default_value_t = 0.5)]
+    overlap: f32,
+
+    /// Window applied to each FFT frame.
+    #[arg(long, value_enum, default_value_t = Window::Hann)]
+    window: Window,
+
+    /// Lowest level shown, in dBFS. Has to be below 0.
+    #[arg(
+        long,
+        default_value_t = -120.0,
+        allow_negative_numbers = true,
+        value_parser = parse_floor
+    )]
+    db_floor: f32,
+}
+
+impl SpectrumArgs {
+    pub fn to_config(&self) -> SpectrumConfig {
+        let fft_size = self.fft_size as usize;
+        let hop_size = match self.hop_size {
+            Some(hop_size) => (hop_size as usize).min(fft_size),
+            None => SpectrumConfig::hop_for_overlap(fft_size, self.overlap),
+        };
+
+        SpectrumConfig {
+            fft_size,
+            hop_size,
+            window: self.window,
+            db_floor: self.db_floor,
+        }
+    }
+}
+
+/// How spectra are grouped into bars.
+#[derive(ClapArgs)]
+pub struct BandArgs {
+    /// Number of bands. Ignored by the octave scales.
+    #[arg(long, default_value_t = 32, value_parser = clap::value_parser!(u32).range(1..=1024))]
+    bands: u32,
+
+    /// Frequency scale the bands are spaced on.
+    #[arg(long, value_enum, default_value_t = BandScale::Log)]
+    band_scale: BandScale,
+
+    /// Lower edge of the lowest band in Hz.
+    #[arg(long, default_value_t = 20.0, value_parser = parse_frequency::<f32>)]
+    min_frequency: f32,
+
+    /// Upper edge of the highest band in Hz.
+    #[arg(long, default_value_t = 20000.0, value_parser = parse_frequency::<f32>)]
+    max_frequency: f32,
+}
+
+impl BandArgs {
+    pub fn to_config(&self) -> BandConfig {
+        BandConfig {
+            scale: self.band_scale,
+            count: self.bands as usize,
+            min_frequency: self.min_frequency,
+            max_frequency: self.max_frequency,
+        }
+    }
+}
+
+/// How the level meters move.
+#[derive(ClapArgs)]
+pub struct MeterArgs {
+    /// Meter ballistics.
+    #[arg(long, value_enum, default_value_t = BallisticsPreset::Digital)]
+    ballistics: BallisticsPreset,
+
+    /// Attack time constant in milliseconds, overriding the ballistics'.
+    #[arg(long, value_parser = parse_milliseconds)]
+    meter_attack: Option<Duration>,
+
+    /// Fall rate in dB per second, overriding the ballistics'.
+    #[arg(long, value_parser = parse_rate)]
+    meter_release: Option<f32>,
+
+    /// Peak hold time in milliseconds, overriding the ballistics'.
+    #[arg(long, value_parser = parse_milliseconds)]
+    meter_hold: Option<Duration>,
+}
+
+impl MeterArgs {
+    pub fn to_ballistics(&self) -> Ballistics {
+        let mut ballistics = self.ballistics.ballistics();
+        if let Some(attack) = self.meter_attack {
+            ballistics.attack = attack;
+        }
+        if let Some(release) = self.meter_release {
+            ballistics.release = Release::DbPerSecond(release);
+        }
+        if let Some(hold) = self.meter_hold {
+            ballistics.hold = hold;
+        }
+        ballistics
+    }
+}
+
+/// Loudness measurement.
+#[derive(ClapArgs)]
+pub struct LoudnessArgs {
+    /// Integrated loudness to compare against in the summary, in LUFS.
+    #[arg(long, default_value_t = -23.0, allow_negative_numbers = true)]
+    pub loudness_target: f64,
+
+    /// Append momentary, short-term and integrated loudness to this file
+    /// every 100 ms, one JSON object per line.
+    #[arg(long)]
+    pub loudness_log: Option<PathBuf>,
+}
+
+/// How the terminal views are shown.
+#[derive(ClapArgs)]
+pub struct DisplayArgs {
+    /// What to show.
+    #[arg(long, value_enum, default_value_t = View::Bars)]
+    view: View,
+
+    /// Frames drawn per second.
+    #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u32).range(1..=240))]
+    fps: u32,
+
+    /// Colors sent to the terminal.
+    #[arg(long, value_enum, default_value_t = ColorMode::Auto)]
+    color: ColorMode,
+
+    /// Milliseconds of audio the oscilloscope and goniometer show.
+    #[arg(long, default_value_t = 20, value_parser = clap::value_parser!(u64).range(1..=10000))]
+    scope_window: u64,
+
+    /// Whether the oscilloscope stacks the left and right channels or
+    /// draws them on top of each other.
+    #[arg(long, value_enum, default_value_t = ScopeLayout::Separate)]
+    scope_layout: ScopeLayout,
+
+    /// Colormap of the spectrogram.
+    #[arg(long, value_enum, default_value_t = Colormap::Viridis)]
+    colormap: Colormap,
+
+    /// Level at the bottom of the spectrogram's colormap, in dBFS.
+    #[arg(long, default_value_t = -100.0, allow_negative_numbers = true)]
+    spectrogram_min: f32,
+
+    /// Level at the top of the spectrogram's colormap, in dBFS.
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    spectrogram_max: f32,
+
+    /// Frequency axis of the spectrogram. It spans --min-frequency to
+    /// --max-frequency.
+    #[arg(long, value_enum, default_value_t = BandScale::Log)]
+    spectrogram_scale: BandScale,
+
+    /// Gain applied to the audio before it's shown, in dB. Loudness and
+    /// silence are measured without it.
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    gain: f32,
+
+    /// How much of the previous bar heights carries over into the next
+    /// spectrum's, from 0 for none to 0.99 for slowly moving bars.
+    #[arg(long, default_value_t = 0.0)]
+    smoothing: f32,
+}
+
+impl DisplayArgs {
+    pub fn to_config(&self) -> DisplayConfig {
+        DisplayConfig {
+            frame_interval: Duration::from_secs(1) / self.fps,
+            color: self.color,
+            view: self.view,
+            scope_window: Duration::from_millis(self.scope_window),
+            scope_layout: self.scope_layout,
+            spectrogram: SpectrogramConfig {
+                colormap: self.colormap,
+                db_min: self.spectrogram_min,
+                db_max: self.spectrogram_max,
+                scale: self.spectrogram_scale,
+            },
+            gain: self.gain,
+            smoothing: self.smoothing.clamp(0.0, 0.99),
+        }
+    }
+}
+
+/// When to stop. Without any of these, runs until interrupted, until the
+/// input file ends, or until `q` is pressed.
+#[derive(ClapArgs)]
+pub struct StopArgs {
+    /// Stop after this many seconds.
+    #[arg(long, value_parser = parse_seconds)]
+    duration: Option<Duration>,
+
+    /// Stop once the audio stayed silent for this many seconds.
+    #[arg(long, value_parser = parse_seconds)]
+    stop_on_silence: Option<Duration>,
+
+    /// Peak level up to which audio counts as silent, in dBFS.
+    #[arg(long, default_value_t = -60.0, allow_negative_numbers = true)]
+    silence_threshold: f32,
+
+    /// Stop after drawing this many frames. Record ignores it.
+    #[arg(long)]
+    frames: Option<u64>,
+}
+
+impl StopArgs {
+    pub fn to_config(&self) -> StopConfig {
+        StopConfig {
+            duration: self.duration,
+            silence: self.stop_on_silence,
+            silence_threshold: self.silence_threshold,
+            frames: self.frames,
+        }
+    }
+}
+
+/// Where diagnostics go.
+#[derive(ClapArgs)]
+pub struct LogArgs {
+    /// Which diagnostics to show: a level out of off, error, warn, info,
+    /// debug and trace, and levels for single targets like
+    /// `warn,stream=debug`. The targets are context, introspect, stream,
+    /// analysis, render and config. While visualizing, they're shown once
+    /// the terminal is restored.
+    #[arg(long, default_value = "warn")]
+    log: LogFilter,
+
+    /// Also append diagnostics to this file, one JSON object per line.
+    #[arg(long)]
+    log_file: Option<PathBuf>,
+}
+
+impl LogArgs {
+    pub fn to_config(&self) -> LogConfig {
+        LogConfig {
+            filter: self.log.clone(),
+            file: self.log_file.clone(),
+        }
+    }
+}
+
+/// Parses a frequency in Hz, which has to be positive.
+fn parse_frequency<T: FromStr + Copy + Into<f64>>(s: &str) -> Result<T, String> {
+    match s.parse::<T>() {
+        Ok(hz) if hz.into().is_finite() && hz.into() > 0.0 => Ok(hz),
+        _ => Err(format!("\"{}\" is not a positive frequency", s)),
+    }
+}
+
+/// Parses a level in dBFS that has to be below full scale.
+fn parse_floor(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(db) if db.is_finite() && db < 0.0 => Ok(db),
+        _ => Err(format!("\"{}\" is not a level below 0 dBFS", s)),
+    }
+}
+
+/// Parses a duration in seconds.
+fn parse_seconds(s: &str) -> Result<Duration, String> {
+    parse_duration(s, 1.0)
+}
+
+/// Parses a duration in milliseconds.
+fn parse_milliseconds(s: &str) -> Result<Duration, String> {
+    parse_duration(s, 1000.0)
+}
+
+/// Parses a duration given in units of which `per_second` make up a
+/// second. Negative, infinite and too long durations are rejected.
+fn parse_duration(s: &str, per_second: f64) -> Result<Duration, String> {
+    s.parse::<f64>()
+        .ok()
+        .and_then(|value| Duration::try_from_secs_f64(value / per_second).ok())
+        .ok_or_else(|| format!("\"{}\" is not a valid duration", s))
+}
+
+/// Parses a rate, which has to be finite and can't be negative.
+fn parse_rate(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(rate) if rate.is_finite() && rate >= 0.0 => Ok(rate),
+        _ => Err(format!("\"{}\" is not a valid rate", s)),
+    }
+}
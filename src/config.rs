@@ -0,0 +1,464 @@
+//! Settings from a TOML file, so a setup doesn't have to be spelled out on
+//! every run.
+//!
+//! The file is `$XDG_CONFIG_HOME/pulse-visualizer/config.toml`, or
+//! `~/.config/pulse-visualizer/config.toml` without `XDG_CONFIG_HOME`. Its
+//! keys are the long command line options without the leading dashes.
+//! Top-level keys apply to every run, and a `[profiles.<name>]` table is
+//! applied on top of them when selected with `--profile <name>`:
+//!
+//! ```toml
+//! device = "FiiO"
+//! fft-size = 4096
+//! color = "truecolor"
+//! loudness-log = "/tmp/loudness.jsonl"
+//!
+//! [profiles.surround]
+//! channel-map = "surround-51"
+//! view = "meters"
+//!
+//! [profiles.test]
+//! generate = "multi-tone"
+//! tones = [50, 440, 5000]
+//! duration = 10
+//! ```
+//!
+//! Flags are `true` or `false`, and options taking several values take a
+//! list. Options given on the command line win over the file, and a
+//! profile's over the top-level ones. A setting is also dropped when a
+//! later layer sets an option it conflicts with, so a profile's `input`
+//! replaces the top-level `device`.
+
+use std::{
+    env,
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use clap::{
+    error::ErrorKind, parser::ValueSource, Arg, ArgMatches, Command, CommandFactory, FromArgMatches,
+};
+use toml::{Table, Value};
+
+use pulse_visualizer::error::Error;
+
+use crate::cli::Args;
+
+/// Options that pick the file, so setting them in it makes no sense.
+const COMMAND_LINE_ONLY: [&str; 3] = ["config", "profile", "no_config"];
+
+/// One option set by the file.
+struct Setting {
+    /// The clap id of the option.
+    id: String,
+    /// The command line arguments standing for it, none for a flag set to
+    /// `false`.
+    args: Vec<String>,
+}
+
+/// Parses the command line on top of the configuration file, and returns
+/// the file's path if there was one. Command line errors exit the way
+/// `Args::parse` does.
+pub fn parse_args() -> Result<(Args, Option<PathBuf>), Error> {
+    let argv: Vec<OsString> = env::args_os().collect();
+    let command = Args::command();
+    let matches = command.clone().get_matches_from(&argv);
+    let args = from_matches(&matches);
+
+    let Some((path, table)) = load(&args)? else {
+        return Ok((args, None));
+    };
+    let profile = args.config.profile.as_deref();
+    let merged = merge(&command, argv, &matches, &path, &table, profile)?;
+    Ok((from_matches(&command.get_matches_from(merged)), Some(path)))
+}
+
+/// Reads the configuration file at `path` again and merges it with the
+/// command line the same way. The command line was fine at startup, so
+/// anything wrong now is the file's fault.
+pub fn reload(path: &Path) -> Result<Args, Error> {
+    let argv: Vec<OsString> = env::args_os().collect();
+    let command = Args::command();
+    let matches = command.clone().get_matches_from(&argv);
+    let args = from_matches(&matches);
+
+    let table = read_table(path)?;
+    let profile = args.config.profile.as_deref();
+    let merged = merge(&command, argv, &matches, path, &table, profile)?;
+    command
+        .try_get_matches_from(merged)
+        .and_then(|matches| Args::from_arg_matches(&matches))
+        .map_err(|err| invalid(path, clap_message(&err)))
+}
+
+/// The command line with the file's settings inserted in front of its
+/// arguments, leaving out those the command line overrides.
+fn merge(
+    command: &Command,
+    argv: Vec<OsString>,
+    matches: &ArgMatches,
+    path: &Path,
+    table: &Table,
+    profile: Option<&str>,
+) -> Result<Vec<OsString>, Error> {
+    let settings = settings(command, path, table, profile)?;
+
+    let given: Vec<&str> = command
+        .get_arguments()
+        .map(|arg| arg.get_id().as_str())
+        .filter(|id| matches.value_source(id) == Some(ValueSource::CommandLine))
+        .collect();
+    let file_args: Vec<OsString> = layer(command, settings, &given)
+        .into_iter()
+        .flat_map(|setting| setting.args)
+        .map(OsString::from)
+        .collect();
+
+    // The file's arguments are checked on their own first, so a bad value
+    // is blamed on the file rather than on the command line. What they
+    // require may still come from the command line, though.
+    if let Err(err) = command
+        .clone()
+        .try_get_matches_from(argv.iter().take(1).chain(&file_args))
+    {
+        if err.kind() != ErrorKind::MissingRequiredArgument {
+            return Err(invalid(path, clap_message(&err)));
+        }
+    }
+
+    let mut argv = argv.into_iter();
+    Ok(argv
+        .next()
+        .into_iter()
+        .chain(file_args)
+        .chain(argv)
+        .collect())
+}
+
+fn from_matches(matches: &ArgMatches) -> Args {
+    Args::from_arg_matches(matches).unwrap_or_else(|err| err.exit())
+}
+
+/// `$XDG_CONFIG_HOME/pulse-visualizer/config.toml`, falling back to
+/// `~/.config` like the XDG base directory spec asks for.
+fn default_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config_home.join("pulse-visualizer").join("config.toml"))
+}
+
+/// Reads the file given with --config, or the default one. A missing
+/// default file just means there are no settings, unless a profile from it
+/// was asked for.
+fn load(args: &Args) -> Result<Option<(PathBuf, Table)>, Error> {
+    let config = &args.config;
+    if config.no_config {
+        return Ok(None);
+    }
+
+    let required = config.config.is_some() || config.profile.is_some();
+    let Some(path) = config.config.clone().or_else(default_path) else {
+        if !required {
+            return Ok(None);
+        }
+        return Err(invalid(
+            Path::new("config.toml"),
+            "neither XDG_CONFIG_HOME nor HOME is set".to_string(),
+        ));
+    };
+
+    match read_table(&path) {
+        Err(Error::Input(_, err)) if err.kind() == io::ErrorKind::NotFound && !required => Ok(None),
+        result => result.map(|table| Some((path, table))),
+    }
+}
+
+fn read_table(path: &Path) -> Result<Table, Error> {
+    let text = fs::read_to_string(path).map_err(|err| Error::Input(path.to_path_buf(), err))?;
+    text.parse().map_err(|err: toml::de::Error| {
+        let message = err.message().trim_end().replace('\n', "; ");
+        let reason = match err.span() {
+            Some(span) => format!("line {}: {}", line_number(&text, span.start), message),
+            None => message,
+        };
+        invalid(path, reason)
+    })
+}
+
+fn line_number(text: &str, offset: usize) -> usize {
+    text[..offset].matches('\n').count() + 1
+}
+
+/// The top-level settings with the selected profile's applied on top.
+/// Every profile is checked, so a typo shows up before it's selected.
+fn settings(
+    command: &Command,
+    path: &Path,
+    table: &Table,
+    profile: Option<&str>,
+) -> Result<Vec<Setting>, Error> {
+    let mut table = table.clone();
+    let profiles = match table.remove("profiles") {
+        Some(Value::Table(profiles)) => profiles,
+        Some(_) => return Err(invalid(path, "`profiles` is not a table".to_string())),
+        None => Table::new(),
+    };
+
+    let settings = parse_settings(command, &table).map_err(|reason| invalid(path, reason))?;
+    let mut overrides = None;
+    for (name, table) in &profiles {
+        let Value::Table(table) = table else {
+            return Err(invalid(path, format!("profile `{}` is not a table", name)));
+        };
+        let profile_settings = parse_settings(command, table)
+            .map_err(|reason| invalid(path, format!("profile `{}`: {}", name, reason)))?;
+        if profile == Some(name) {
+            overrides = Some(profile_settings);
+        }
+    }
+
+    let Some(name) = profile else {
+        return Ok(settings);
+    };
+    let Some(overrides) = overrides else {
+        let reason = if profiles.is_empty() {
+            format!("no profile named `{}`, the file has none", name)
+        } else {
+            let names: Vec<&str> = profiles.keys().map(String::as_str).collect();
+            format!(
+                "no profile named `{}`, available are {}",
+                name,
+                names.join(", ")
+            )
+        };
+        return Err(invalid(path, reason));
+    };
+
+    let ids: Vec<&str> = overrides
+        .iter()
+        .map(|setting| setting.id.as_str())
+        .collect();
+    let mut settings = layer(command, settings, &ids);
+    settings.extend(overrides);
+    Ok(settings)
+}
+
+/// Turns the keys of `table` into command line arguments.
+fn parse_settings(command: &Command, table: &Table) -> Result<Vec<Setting>, String> {
+    table
+        .iter()
+        .map(|(key, value)| {
+            let arg = command
+                .get_arguments()
+                .filter(|arg| !COMMAND_LINE_ONLY.contains(&arg.get_id().as_str()))
+                .find(|arg| arg.get_long() == Some(key))
+                .ok_or_else(|| format!("unknown setting `{}`", key))?;
+
+            let args = match (arg.get_action().takes_values(), value) {
+                (false, Value::Boolean(true)) => vec![format!("--{}", key)],
+                (false, Value::Boolean(false)) => Vec::new(),
+                (false, _) => return Err(format!("`{}` has to be true or false", key)),
+                (true, value) => match option_value(arg, value) {
+                    Some(value) => vec![format!("--{}={}", key, value)],
+                    None => {
+                        return Err(format!("`{}` has to be a string or a number", key));
+                    }
+                },
+            };
+
+            Ok(Setting {
+                id: arg.get_id().to_string(),
+                args,
+            })
+        })
+        .collect()
+}
+
+/// The command line value standing for `value`: strings and numbers as
+/// they are, and lists comma separated for options taking several values.
+fn option_value(arg: &Arg, value: &Value) -> Option<String> {
+    match value {
+        Value::Array(items) if arg.get_value_delimiter().is_some() => {
+            let items: Option<Vec<String>> = items.iter().map(scalar).collect();
+            items.map(|items| items.join(","))
+        }
+        value => scalar(value),
+    }
+}
+
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value.clone()),
+        Value::Integer(value) => Some(value.to_string()),
+        Value::Float(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// Drops the settings a later layer replaces: those for options it sets,
+/// and those for options conflicting with one it sets.
+fn layer(command: &Command, settings: Vec<Setting>, later: &[&str]) -> Vec<Setting> {
+    settings
+        .into_iter()
+        .filter(|setting| {
+            !later
+                .iter()
+                .any(|id| *id == setting.id || conflict(command, id, &setting.id))
+        })
+        .collect()
+}
+
+fn conflict(command: &Command, a: &str, b: &str) -> bool {
+    let conflicts_with = |a: &str, b: &str| {
+        command
+            .get_arguments()
+            .find(|arg| arg.get_id().as_str() == a)
+            .is_some_and(|arg| {
+                command
+                    .get_arg_conflicts_with(arg)
+                    .iter()
+                    .any(|other| other.get_id().as_str() == b)
+            })
+    };
+    conflicts_with(a, b) || conflicts_with(b, a)
+}
+
+fn invalid(path: &Path, reason: String) -> Error {
+    Error::Config(path.to_path_buf(), reason)
+}
+
+/// The first line of a clap error, without its `error: ` prefix.
+fn clap_message(err: &clap::Error) -> String {
+    let text = err.to_string();
+    let line = text.lines().next().unwrap_or_default();
+    line.strip_prefix("error: ").unwrap_or(line).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use pulse_visualizer::{device::DeviceSelector, source::SourceConfig};
+
+    use super::*;
+
+    /// Parses `argv` on top of the configuration file `file`.
+    fn parse(argv: &[&str], file: &str) -> Result<Args, Error> {
+        let command = Args::command();
+        let argv: Vec<OsString> = ["pulse-visualizer"]
+            .iter()
+            .chain(argv)
+            .map(OsString::from)
+            .collect();
+        let matches = command.clone().try_get_matches_from(&argv).unwrap();
+        let profile = from_matches(&matches).config.profile;
+        let table = file.parse().unwrap();
+        let merged = merge(
+            &command,
+            argv,
+            &matches,
+            Path::new("config.toml"),
+            &table,
+            profile.as_deref(),
+        )?;
+        Ok(from_matches(&command.try_get_matches_from(merged).unwrap()))
+    }
+
+    fn reason(result: Result<Args, Error>) -> String {
+        match result {
+            Err(Error::Config(_, reason)) => reason,
+            Err(err) => panic!("unexpected error {}", err),
+            Ok(_) => panic!("the configuration was accepted"),
+        }
+    }
+
+    fn duration(args: &Args) -> Option<Duration> {
+        args.stop.to_config().duration
+    }
+
+    #[test]
+    fn command_line_overrides_the_file() {
+        let file = "duration = 5\nfft-size = 4096";
+
+        let args = parse(&["--duration", "1"], file).unwrap();
+        assert_eq!(duration(&args), Some(Duration::from_secs(1)));
+        assert_eq!(args.settings().analysis.spectrum.fft_size, 4096);
+
+        let args = parse(&[], file).unwrap();
+        assert_eq!(duration(&args), Some(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn profile_overrides_top_level_settings() {
+        let file = "duration = 5\nfft-size = 4096\n[profiles.long]\nduration = 10";
+
+        let args = parse(&["--profile", "long"], file).unwrap();
+        assert_eq!(duration(&args), Some(Duration::from_secs(10)));
+        assert_eq!(args.settings().analysis.spectrum.fft_size, 4096);
+
+        let args = parse(&["--profile", "long", "--duration", "1"], file).unwrap();
+        assert_eq!(duration(&args), Some(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn conflicting_options_drop_earlier_settings() {
+        let file = "device = \"FiiO\"\n[profiles.file]\ninput = \"a.wav\"";
+
+        let args = parse(&["--profile", "file"], file).unwrap();
+        assert!(matches!(args.settings().source, SourceConfig::File { .. }));
+
+        let args = parse(&["--generate", "sine"], file).unwrap();
+        assert!(matches!(args.settings().source, SourceConfig::Generator(_)));
+
+        let args = parse(&[], file).unwrap();
+        let SourceConfig::Capture(config) = args.settings().source else {
+            panic!("not recording a sink");
+        };
+        assert_eq!(config.selector, "FiiO".parse::<DeviceSelector>().unwrap());
+    }
+
+    #[test]
+    fn rejects_unknown_settings_and_profiles() {
+        assert_eq!(reason(parse(&[], "fft = 4096")), "unknown setting `fft`");
+        assert_eq!(
+            reason(parse(&[], "[profiles.test]\nfft = 4096")),
+            "profile `test`: unknown setting `fft`"
+        );
+        assert_eq!(
+            reason(parse(
+                &["--profile", "loud"],
+                "[profiles.test]\n[profiles.long]"
+            )),
+            "no profile named `loud`, available are long, test"
+        );
+        assert_eq!(
+            reason(parse(&["--profile", "loud"], "duration = 5")),
+            "no profile named `loud`, the file has none"
+        );
+        assert_eq!(
+            reason(parse(&[], "profile = \"test\"")),
+            "unknown setting `profile`"
+        );
+    }
+
+    #[test]
+    fn flags_can_be_turned_off() {
+        let follows = |args: Args| match args.settings().source {
+            SourceConfig::Capture(config) => config.follow_default,
+            _ => panic!("not recording a sink"),
+        };
+
+        assert!(follows(parse(&[], "follow-default = true").unwrap()));
+        assert!(!follows(parse(&[], "follow-default = false").unwrap()));
+        assert!(follows(
+            parse(&["--follow-default"], "follow-default = false").unwrap()
+        ));
+        assert_eq!(
+            reason(parse(&[], "follow-default = \"yes\"")),
+            "`follow-default` has to be true or false"
+        );
+    }
+}
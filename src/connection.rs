@@ -0,0 +1,371 @@
+use std::{
+    cell::{Cell, RefCell},
+    ops::Deref,
+    rc::Rc,
+    sync::mpsc::{self, Receiver, TryRecvError},
+    time::Duration,
+};
+
+use pulse::{
+    channelmap::Map,
+    context::{
+        subscribe::{Facility, InterestMaskSet, Operation},
+        Context, FlagSet as ContextFlagSet, State,
+    },
+    def::Retval,
+    error::{Code, PAErr},
+    mainloop::standard::{IterateResult, Mainloop},
+    proplist::{properties, Proplist},
+    sample::Spec,
+    stream::{FlagSet as StreamFlagSet, PeekResult, State as StreamStateKind, Stream},
+    time::MicroSeconds,
+};
+
+use crate::{
+    device::{ServerDefaults, Sink, Source},
+    error::Error,
+    logging,
+    server::{Peek, RecordStream, Server, StreamState},
+};
+
+/// A context connected to the sound server, together with the mainloop that
+/// drives it.
+///
+/// The mainloop is only ever iterated blocking, either until the next event
+/// or with a timeout, so waiting on the server costs no CPU.
+pub struct Connection {
+    // The context has to go before the mainloop it was created on, so it's
+    // declared first.
+    pub context: Rc<RefCell<Context>>,
+    pub mainloop: Rc<RefCell<Mainloop>>,
+    failed: Rc<Cell<bool>>,
+    default_changed: Rc<Cell<bool>>,
+}
+
+impl Connection {
+    /// Connects to the default server and waits until the context is ready.
+    pub fn connect() -> Result<Connection, Error> {
+        let internal = || Error::Connection(PAErr::from(Code::Internal));
+
+        let mut proplist = Proplist::new().ok_or_else(internal)?;
+        proplist
+            .set_str(properties::APPLICATION_NAME, "PulseVisualizer")
+            .map_err(|_| internal())?;
+
+        let mainloop = Rc::new(RefCell::new(Mainloop::new().ok_or_else(internal)?));
+
+        let context = Rc::new(RefCell::new(
+            Context::new_with_proplist(
+                mainloop.borrow().deref(),
+                "PulseVisualizerContext",
+                &proplist,
+            )
+            .ok_or_else(internal)?,
+        ));
+
+        // Remembers a failed or terminated context, so loops iterating the
+        // mainloop notice a server that went away. The callback also fires
+        // synchronously from `connect`, while the context is still borrowed;
+        // that transition is never a failure, so it's skipped.
+        let failed = Rc::new(Cell::new(false));
+        {
+            let failed = failed.clone();
+            let context_ref = Rc::downgrade(&context);
+            context
+                .borrow_mut()
+                .set_state_callback(Some(Box::new(move || {
+                    let Some(context) = context_ref.upgrade() else {
+                        return;
+                    };
+                    let Ok(context) = context.try_borrow() else {
+                        return;
+                    };
+                    let state = context.get_state();
+                    log::debug!(target: logging::CONTEXT, "Context is {:?}.", state);
+                    if matches!(state, State::Failed | State::Terminated) {
+                        failed.set(true);
+                    }
+                })));
+        }
+
+        log::debug!(target: logging::CONTEXT, "Connecting to the sound server.");
+        context
+            .borrow_mut()
+            .connect(None, ContextFlagSet::NOFLAGS, None)
+            .map_err(Error::Connection)?;
+
+        let connection = Connection {
+            context,
+            mainloop,
+            failed,
+            default_changed: Rc::new(Cell::new(false)),
+        };
+
+        loop {
+            match connection.mainloop.borrow_mut().iterate(true) {
+                IterateResult::Err(err) => return Err(Error::Connection(err)),
+                IterateResult::Quit(_) => return Err(Error::Disconnected),
+                IterateResult::Success(_) => {}
+            }
+
+            if connection.has_failed() {
+                return Err(Error::Connection(connection.context.borrow().errno()));
+            }
+
+            if connection.context.borrow().get_state() == State::Ready {
+                break;
+            }
+        }
+
+        log::info!(target: logging::CONTEXT, "Connected to the sound server.");
+        Ok(connection)
+    }
+
+    /// Whether the context has failed or was terminated since connecting.
+    pub fn has_failed(&self) -> bool {
+        self.failed.get()
+    }
+
+    /// Iterates the mainloop until an introspection callback delivers its
+    /// result. Callbacks send `None` when the server reported an error.
+    fn wait_for<T>(&self, rx: Receiver<Option<T>>) -> Result<T, Error> {
+        loop {
+            if let IterateResult::Err(_) | IterateResult::Quit(_) =
+                self.mainloop.borrow_mut().iterate(true)
+            {
+                return Err(Error::Disconnected);
+            }
+
+            if self.has_failed() {
+                return Err(Error::Disconnected);
+            }
+
+            match rx.try_recv() {
+                Ok(Some(result)) => break Ok(result),
+                Ok(None) => {
+                    let err = self.context.borrow().errno();
+                    log::debug!(target: logging::INTROSPECT, "Request failed: {}", err);
+                    break Err(Error::Introspection(err));
+                }
+                Err(TryRecvError::Empty) => {}
+                // The operation was dropped without calling back, which only
+                // happens when the context goes away.
+                Err(TryRecvError::Disconnected) => break Err(Error::Disconnected),
+            }
+        }
+    }
+}
+
+impl Server for Connection {
+    type Stream = PulseStream;
+
+    fn sinks(&self) -> Result<Vec<Sink>, Error> {
+        let (tx, rx) = mpsc::channel();
+        let mut sinks = Vec::new();
+        self.context
+            .borrow_mut()
+            .introspect()
+            .get_sink_info_list(move |result| match result {
+                pulse::callbacks::ListResult::Item(item) => sinks.push(Sink::from(item)),
+                pulse::callbacks::ListResult::End => {
+                    log::debug!(target: logging::INTROSPECT, "Listed {} sinks.", sinks.len());
+                    let _ = tx.send(Some(std::mem::take(&mut sinks)));
+                }
+                pulse::callbacks::ListResult::Error => {
+                    let _ = tx.send(None);
+                }
+            });
+
+        self.wait_for(rx)
+    }
+
+    fn sources(&self) -> Result<Vec<Source>, Error> {
+        let (tx, rx) = mpsc::channel();
+        let mut sources = Vec::new();
+        self.context
+            .borrow_mut()
+            .introspect()
+            .get_source_info_list(move |result| match result {
+                pulse::callbacks::ListResult::Item(item) => sources.push(Source::from(item)),
+                pulse::callbacks::ListResult::End => {
+                    log::debug!(target: logging::INTROSPECT, "Listed {} sources.", sources.len());
+                    let _ = tx.send(Some(std::mem::take(&mut sources)));
+                }
+                pulse::callbacks::ListResult::Error => {
+                    let _ = tx.send(None);
+                }
+            });
+
+        self.wait_for(rx)
+    }
+
+    fn server_defaults(&self) -> Result<ServerDefaults, Error> {
+        let (tx, rx) = mpsc::channel();
+        self.context
+            .borrow_mut()
+            .introspect()
+            .get_server_info(move |info| {
+                let defaults = ServerDefaults::from(info);
+                log::debug!(
+                    target: logging::INTROSPECT,
+                    "Default sink {:?}, default source {:?}.",
+                    defaults.sink,
+                    defaults.source
+                );
+                let _ = tx.send(Some(defaults));
+            });
+
+        self.wait_for(rx)
+    }
+
+    /// Moves the source output (record stream) with `index` to the source
+    /// named `source_name`.
+    fn move_source_output(&self, index: u32, source_name: &str) -> Result<(), Error> {
+        log::debug!(
+            target: logging::INTROSPECT,
+            "Moving source output {} to {}.",
+            index,
+            source_name
+        );
+        let (tx, rx) = mpsc::channel();
+        self.context
+            .borrow_mut()
+            .introspect()
+            .move_source_output_by_name(
+                index,
+                source_name,
+                Some(Box::new(move |success| {
+                    let _ = tx.send(success.then_some(()));
+                })),
+            );
+
+        self.wait_for(rx)
+    }
+
+    /// The stream's state is polled rather than watched through callbacks,
+    /// and its data read after each iteration, so nothing runs while the
+    /// stream is borrowed.
+    fn record(&self, source: &str, spec: &Spec, map: &Map) -> Result<PulseStream, Error> {
+        let mut stream = Stream::new(
+            &mut self.context.borrow_mut(),
+            "PulseVisualizer",
+            spec,
+            Some(map),
+        )
+        .ok_or_else(|| Error::Stream(self.context.borrow().errno()))?;
+
+        log::debug!(
+            target: logging::STREAM,
+            "Connecting a record stream to {} in {} ({}).",
+            source,
+            spec.print(),
+            map.print()
+        );
+        stream
+            .connect_record(Some(source), None, StreamFlagSet::NOFLAGS)
+            .map_err(Error::Stream)?;
+
+        Ok(PulseStream {
+            stream,
+            context: self.context.clone(),
+        })
+    }
+
+    /// Server changes include the default sink changing. New and removed
+    /// sinks are watched too, since a default that shows up late or goes
+    /// away changes what the default resolves to.
+    fn watch_default_sink(&self) {
+        let default_changed = self.default_changed.clone();
+        let mut context = self.context.borrow_mut();
+        context.set_subscribe_callback(Some(Box::new(move |facility, operation, index| {
+            log::trace!(
+                target: logging::CONTEXT,
+                "{:?} {:?} {}.",
+                facility,
+                operation,
+                index
+            );
+            match (facility, operation) {
+                (Some(Facility::Server), _)
+                | (Some(Facility::Sink), Some(Operation::New | Operation::Removed)) => {
+                    default_changed.set(true)
+                }
+                _ => {}
+            }
+        })));
+        context.subscribe(InterestMaskSet::SERVER | InterestMaskSet::SINK, |_| {});
+    }
+
+    fn default_sink_changed(&self) -> bool {
+        self.default_changed.replace(false)
+    }
+
+    /// Runs one mainloop iteration, blocking until an event arrives or
+    /// `timeout` passes.
+    fn iterate(&self, timeout: Duration) -> Result<(), Error> {
+        let timeout = MicroSeconds(timeout.as_micros().try_into().unwrap_or(u64::MAX));
+        let iterated = {
+            let mut mainloop = self.mainloop.borrow_mut();
+            mainloop.prepare(Some(timeout)).is_ok()
+                && mainloop.poll().is_ok()
+                && mainloop.dispatch().is_ok()
+        };
+
+        if !iterated || self.has_failed() {
+            return Err(Error::Disconnected);
+        }
+        Ok(())
+    }
+
+    fn disconnect(&self) {
+        log::debug!(target: logging::CONTEXT, "Disconnecting from the sound server.");
+        self.context.borrow_mut().set_state_callback(None);
+        self.context.borrow_mut().disconnect();
+        self.mainloop.borrow_mut().quit(Retval(0));
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.disconnect();
+    }
+}
+
+/// A record stream on a [`Connection`].
+pub struct PulseStream {
+    stream: Stream,
+    context: Rc<RefCell<Context>>,
+}
+
+impl RecordStream for PulseStream {
+    fn state(&self) -> StreamState {
+        match self.stream.get_state() {
+            StreamStateKind::Ready => StreamState::Ready,
+            StreamStateKind::Failed | StreamStateKind::Terminated => {
+                StreamState::Failed(self.context.borrow().errno())
+            }
+            _ => StreamState::Connecting,
+        }
+    }
+
+    fn read(&mut self) -> Result<Peek, Error> {
+        let peek = match self.stream.peek().map_err(Error::Stream)? {
+            PeekResult::Empty => return Ok(Peek::Empty),
+            PeekResult::Hole(len) => {
+                log::debug!(target: logging::STREAM, "Hole of {} bytes.", len);
+                Peek::Hole(len)
+            }
+            PeekResult::Data(data) => Peek::Data(data.to_vec()),
+        };
+        self.stream.discard().map_err(Error::Stream)?;
+        Ok(peek)
+    }
+
+    fn index(&self) -> Option<u32> {
+        self.stream.get_index()
+    }
+
+    fn disconnect(&mut self) -> Result<(), Error> {
+        self.stream.disconnect().map_err(Error::Stream)
+    }
+}
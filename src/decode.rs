@@ -0,0 +1,216 @@
+use pulse::{
+    channelmap::{Map, Position},
+    sample::{Format, Spec},
+};
+
+use crate::error::Error;
+
+/// Deinterleaved `f32` samples in the range -1.0..=1.0, one `Vec` per
+/// channel, all of the same length. `positions` says which speaker each
+/// channel belongs to.
+#[derive(Debug, Clone, Default)]
+pub struct AudioBlock {
+    pub rate: u32,
+    pub positions: Vec<Position>,
+    pub channels: Vec<Vec<f32>>,
+}
+
+impl AudioBlock {
+    /// An empty block with room for `capacity` frames per channel.
+    pub fn with_capacity(rate: u32, positions: &[Position], capacity: usize) -> Self {
+        AudioBlock {
+            rate,
+            positions: positions.to_vec(),
+            channels: vec![Vec::with_capacity(capacity); positions.len()],
+        }
+    }
+
+    /// Number of frames, i.e. samples per channel.
+    pub fn len(&self) -> usize {
+        self.channels.first().map_or(0, Vec::len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Multiplies every sample by `factor`.
+    pub fn amplify(&mut self, factor: f32) {
+        for sample in self.channels.iter_mut().flatten() {
+            *sample *= factor;
+        }
+    }
+
+    /// Iterates over the frames in order.
+    pub fn frames(&self) -> impl Iterator<Item = Frame<'_>> + '_ {
+        (0..self.len()).map(move |index| Frame { block: self, index })
+    }
+}
+
+/// One sample of every channel at the same point in time.
+#[derive(Debug, Clone, Copy)]
+pub struct Frame<'a> {
+    block: &'a AudioBlock,
+    index: usize,
+}
+
+impl Frame<'_> {
+    pub fn samples(&self) -> impl Iterator<Item = f32> + '_ {
+        self.block
+            .channels
+            .iter()
+            .map(move |channel| channel[self.index])
+    }
+}
+
+/// Whether [`Decoder`] can decode samples in `format`.
+pub fn is_supported(format: Format) -> bool {
+    SampleFormat::from_format(format).is_some()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SampleFormat {
+    U8,
+    S16le,
+    S24le,
+    S32le,
+    F32le,
+}
+
+impl SampleFormat {
+    fn from_format(format: Format) -> Option<Self> {
+        match format {
+            Format::U8 => Some(SampleFormat::U8),
+            Format::S16le => Some(SampleFormat::S16le),
+            Format::S24le => Some(SampleFormat::S24le),
+            Format::S32le => Some(SampleFormat::S32le),
+            Format::F32le => Some(SampleFormat::F32le),
+            _ => None,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            SampleFormat::U8 => 1,
+            SampleFormat::S16le => 2,
+            SampleFormat::S24le => 3,
+            SampleFormat::S32le | SampleFormat::F32le => 4,
+        }
+    }
+
+    fn silence(self) -> u8 {
+        match self {
+            SampleFormat::U8 => 0x80,
+            _ => 0,
+        }
+    }
+
+    fn decode(self, bytes: &[u8]) -> f32 {
+        match self {
+            SampleFormat::U8 => (bytes[0] as f32 - 128.0) / 128.0,
+            SampleFormat::S16le => i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0,
+            SampleFormat::S24le => {
+                i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) as f32 / 2147483648.0
+            }
+            SampleFormat::S32le => {
+                i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32 / 2147483648.0
+            }
+            SampleFormat::F32le => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        }
+    }
+}
+
+/// Turns the raw bytes of a record stream into [`AudioBlock`]s.
+///
+/// Fragments handed out by the server don't have to end on a frame
+/// boundary, so a trailing partial frame is kept and completed by the next
+/// call.
+#[derive(Debug)]
+pub struct Decoder {
+    format: SampleFormat,
+    positions: Vec<Position>,
+    rate: u32,
+    pending: Vec<u8>,
+}
+
+impl Decoder {
+    /// Supports U8, S16LE, S24LE, S32LE and F32LE. `map` must have as many
+    /// channels as `spec`.
+    pub fn new(spec: &Spec, map: &Map) -> Result<Self, Error> {
+        let format = SampleFormat::from_format(spec.format).ok_or_else(|| {
+            Error::InvalidSpec(format!(
+                "cannot decode sample format {}",
+                spec.format.to_string().unwrap_or_default()
+            ))
+        })?;
+
+        if map.get().len() != spec.channels as usize {
+            return Err(Error::InvalidSpec(format!(
+                "channel map {} does not have {} channels",
+                map.print(),
+                spec.channels
+            )));
+        }
+
+        Ok(Decoder {
+            format,
+            positions: map.get().to_vec(),
+            rate: spec.rate,
+            pending: Vec::new(),
+        })
+    }
+
+    fn frame_size(&self) -> usize {
+        self.format.size() * self.positions.len()
+    }
+
+    /// Decodes all complete frames in `data`, including a partial frame left
+    /// over from the previous call.
+    pub fn decode(&mut self, data: &[u8]) -> AudioBlock {
+        let frame_size = self.frame_size();
+
+        let mut data = data;
+        let mut block = AudioBlock::with_capacity(
+            self.rate,
+            &self.positions,
+            (self.pending.len() + data.len()) / frame_size,
+        );
+
+        if !self.pending.is_empty() {
+            let missing = (frame_size - self.pending.len()).min(data.len());
+            self.pending.extend_from_slice(&data[..missing]);
+            data = &data[missing..];
+
+            if self.pending.len() < frame_size {
+                return block;
+            }
+
+            let frame = std::mem::take(&mut self.pending);
+            self.decode_frames(&frame, &mut block);
+        }
+
+        let whole = data.len() - data.len() % frame_size;
+        self.decode_frames(&data[..whole], &mut block);
+        self.pending.extend_from_slice(&data[whole..]);
+
+        block
+    }
+
+    /// Produces silence for a hole of `len` bytes, so timing is preserved.
+    pub fn decode_hole(&mut self, len: usize) -> AudioBlock {
+        self.decode(&vec![self.format.silence(); len])
+    }
+
+    fn decode_frames(&self, data: &[u8], block: &mut AudioBlock) {
+        let sample_size = self.format.size();
+        for frame in data.chunks_exact(self.frame_size()) {
+            for (channel, bytes) in block
+                .channels
+                .iter_mut()
+                .zip(frame.chunks_exact(sample_size))
+            {
+                channel.push(self.format.decode(bytes));
+            }
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use std::{fmt, str::FromStr};
+
+use pulse::{
+    channelmap::{Map, Position},
+    context::introspect::{ServerInfo, SinkInfo, SourceInfo},
+    sample::Spec,
+};
+use regex::Regex;
+use serde::Serialize;
+
+/// Sample format, rate and channel count of a device.
+#[derive(Debug, Clone, Serialize)]
+pub struct SampleSpec {
+    pub format: String,
+    pub rate: u32,
+    pub channels: u8,
+}
+
+impl From<&Spec> for SampleSpec {
+    fn from(spec: &Spec) -> Self {
+        SampleSpec {
+            format: spec.format.to_string().unwrap_or_default().to_string(),
+            rate: spec.rate,
+            channels: spec.channels,
+        }
+    }
+}
+
+impl fmt::Display for SampleSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}ch {}Hz", self.format, self.channels, self.rate)
+    }
+}
+
+fn channel_names(map: &Map) -> Vec<String> {
+    map.get()
+        .iter()
+        .map(|&position| {
+            Position::to_string(position)
+                .unwrap_or_default()
+                .to_string()
+        })
+        .collect()
+}
+
+/// A sink as reported by the server.
+#[derive(Debug, Clone, Serialize)]
+pub struct Sink {
+    pub index: u32,
+    pub name: String,
+    pub description: String,
+    pub sample_spec: SampleSpec,
+    pub channel_map: Vec<String>,
+    pub monitor_source_name: Option<String>,
+    /// The sink's spec and map as the server reported them, which recording
+    /// defaults to.
+    #[serde(skip)]
+    pub native_spec: Spec,
+    #[serde(skip)]
+    pub native_channel_map: Map,
+}
+
+impl From<&SinkInfo<'_>> for Sink {
+    fn from(info: &SinkInfo) -> Self {
+        Sink {
+            index: info.index,
+            name: info.name.as_deref().unwrap_or_default().to_string(),
+            description: info.description.as_deref().unwrap_or_default().to_string(),
+            sample_spec: SampleSpec::from(&info.sample_spec),
+            channel_map: channel_names(&info.channel_map),
+            monitor_source_name: info.monitor_source_name.as_deref().map(str::to_string),
+            native_spec: info.sample_spec,
+            native_channel_map: info.channel_map,
+        }
+    }
+}
+
+/// A source as reported by the server. Monitor sources have
+/// `monitor_of_sink_name` set.
+#[derive(Debug, Clone, Serialize)]
+pub struct Source {
+    pub index: u32,
+    pub name: String,
+    pub description: String,
+    pub sample_spec: SampleSpec,
+    pub channel_map: Vec<String>,
+    pub monitor_of_sink_name: Option<String>,
+}
+
+impl From<&SourceInfo<'_>> for Source {
+    fn from(info: &SourceInfo) -> Self {
+        Source {
+            index: info.index,
+            name: info.name.as_deref().unwrap_or_default().to_string(),
+            description: info.description.as_deref().unwrap_or_default().to_string(),
+            sample_spec: SampleSpec::from(&info.sample_spec),
+            channel_map: channel_names(&info.channel_map),
+            monitor_of_sink_name: info.monitor_of_sink_name.as_deref().map(str::to_string),
+        }
+    }
+}
+
+/// The server's default sink and source names.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ServerDefaults {
+    pub sink: Option<String>,
+    pub source: Option<String>,
+}
+
+impl From<&ServerInfo<'_>> for ServerDefaults {
+    fn from(info: &ServerInfo) -> Self {
+        ServerDefaults {
+            sink: info.default_sink_name.as_deref().map(str::to_string),
+            source: info.default_source_name.as_deref().map(str::to_string),
+        }
+    }
+}
+
+/// Chooses which sink gets visualized.
+///
+/// Parsed from the command line:
+/// - `@DEFAULT_SINK@` / `@DEFAULT_MONITOR@`: the server's default sink,
+/// - a plain number: the sink with that index,
+/// - `/pattern/`: the first sink whose name matches the regex,
+/// - anything else: the sink with exactly that name, or else the single sink
+///   whose name contains it.
+#[derive(Debug, Clone)]
+pub enum DeviceSelector {
+    DefaultSink,
+    DefaultMonitor,
+    Index(u32),
+    Name(String),
+    Regex(Regex),
+}
+
+impl FromStr for DeviceSelector {
+    type Err = regex::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let selector = match s {
+            "@DEFAULT_SINK@" => DeviceSelector::DefaultSink,
+            "@DEFAULT_MONITOR@" => DeviceSelector::DefaultMonitor,
+            _ if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) => {
+                // All digits, so parsing can only fail on overflow.
+                match s.parse() {
+                    Ok(index) => DeviceSelector::Index(index),
+                    Err(_) => DeviceSelector::Name(s.to_string()),
+                }
+            }
+            _ if s.len() >= 2 && s.starts_with('/') && s.ends_with('/') => {
+                DeviceSelector::Regex(Regex::new(&s[1..s.len() - 1])?)
+            }
+            _ => DeviceSelector::Name(s.to_string()),
+        };
+
+        Ok(selector)
+    }
+}
+
+impl fmt::Display for DeviceSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeviceSelector::DefaultSink => write!(f, "@DEFAULT_SINK@"),
+            DeviceSelector::DefaultMonitor => write!(f, "@DEFAULT_MONITOR@"),
+            DeviceSelector::Index(index) => write!(f, "{}", index),
+            DeviceSelector::Name(name) => write!(f, "{}", name),
+            DeviceSelector::Regex(regex) => write!(f, "/{}/", regex),
+        }
+    }
+}
+
+/// Regexes are equal if their patterns are.
+impl PartialEq for DeviceSelector {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (DeviceSelector::DefaultSink, DeviceSelector::DefaultSink)
+            | (DeviceSelector::DefaultMonitor, DeviceSelector::DefaultMonitor) => true,
+            (DeviceSelector::Index(a), DeviceSelector::Index(b)) => a == b,
+            (DeviceSelector::Name(a), DeviceSelector::Name(b)) => a == b,
+            (DeviceSelector::Regex(a), DeviceSelector::Regex(b)) => a.as_str() == b.as_str(),
+            _ => false,
+        }
+    }
+}
+
+impl DeviceSelector {
+    /// Picks a sink out of `sinks`. `default_sink` is the server's default
+    /// sink name, used by the `@DEFAULT_*@` selectors.
+    pub fn select<'a>(
+        &self,
+        sinks: &'a [Sink],
+        default_sink: Option<&str>,
+    ) -> Result<&'a Sink, SelectError> {
+        let failure = |reason| SelectError {
+            selector: self.to_string(),
+            reason,
+            available: sinks.to_vec(),
+        };
+
+        match self {
+            DeviceSelector::DefaultSink | DeviceSelector::DefaultMonitor => {
+                let default_sink = default_sink.ok_or_else(|| failure(SelectFailure::NoDefault))?;
+                sinks
+                    .iter()
+                    .find(|sink| sink.name == default_sink)
+                    .ok_or_else(|| failure(SelectFailure::NoMatch))
+            }
+            DeviceSelector::Index(index) => sinks
+                .iter()
+                .find(|sink| sink.index == *index)
+                .ok_or_else(|| failure(SelectFailure::NoMatch)),
+            DeviceSelector::Name(name) => {
+                if let Some(sink) = sinks.iter().find(|sink| &sink.name == name) {
+                    return Ok(sink);
+                }
+
+                let matches: Vec<&Sink> = sinks
+                    .iter()
+                    .filter(|sink| sink.name.contains(name.as_str()))
+                    .collect();
+                match matches.as_slice() {
+                    [] => Err(failure(SelectFailure::NoMatch)),
+                    [sink] => Ok(sink),
+                    _ => Err(failure(SelectFailure::Ambiguous(
+                        matches.iter().map(|sink| sink.name.clone()).collect(),
+                    ))),
+                }
+            }
+            DeviceSelector::Regex(regex) => sinks
+                .iter()
+                .find(|sink| regex.is_match(&sink.name))
+                .ok_or_else(|| failure(SelectFailure::NoMatch)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum SelectFailure {
+    NoMatch,
+    NoDefault,
+    Ambiguous(Vec<String>),
+    NoMonitor,
+}
+
+/// Returned when a [`DeviceSelector`] can't be resolved. Displays the sinks
+/// that were available so the user can pick one.
+#[derive(Debug, Clone)]
+pub struct SelectError {
+    pub selector: String,
+    pub reason: SelectFailure,
+    pub available: Vec<Sink>,
+}
+
+impl fmt::Display for SelectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.reason {
+            SelectFailure::NoMatch => write!(f, "No sink matches \"{}\".", self.selector)?,
+            SelectFailure::NoDefault => write!(f, "Server has no default sink.")?,
+            SelectFailure::Ambiguous(names) => write!(
+                f,
+                "\"{}\" matches more than one sink: {}.",
+                self.selector,
+                names.join(", ")
+            )?,
+            SelectFailure::NoMonitor => {
+                write!(f, "Sink \"{}\" has no monitor source.", self.selector)?
+            }
+        }
+
+        if self.available.is_empty() {
+            return write!(f, "\nNo sinks available.");
+        }
+
+        write!(f, "\nAvailable sinks:")?;
+        for sink in &self.available {
+            write!(f, "\n  {:>3}  {}", sink.index, sink.name)?;
+            if !sink.description.is_empty() {
+                write!(f, " ({})", sink.description)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for SelectError {}
+
+/// Resolves `selector` to the sink and the name of its monitor source, which
+/// is what the record stream connects to.
+pub fn resolve_monitor<'a>(
+    selector: &DeviceSelector,
+    sinks: &'a [Sink],
+    default_sink: Option<&str>,
+) -> Result<(&'a Sink, String), SelectError> {
+    let sink = selector.select(sinks, default_sink)?;
+    match &sink.monitor_source_name {
+        Some(monitor) => Ok((sink, monitor.clone())),
+        None => Err(SelectError {
+            selector: sink.name.clone(),
+            reason: SelectFailure::NoMonitor,
+            available: sinks.to_vec(),
+        }),
+    }
+}
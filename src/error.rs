@@ -0,0 +1,92 @@
+use std::{fmt, io, path::PathBuf};
+
+use pulse::error::PAErr;
+
+use crate::device::SelectError;
+
+/// Everything that can stop the visualizer.
+#[derive(Debug)]
+pub enum Error {
+    /// The context couldn't be set up or never became ready, e.g. because no
+    /// server is running.
+    Connection(PAErr),
+    /// The device selector matched nothing usable.
+    NoDevice(SelectError),
+    /// Listing devices or querying the server failed.
+    Introspection(PAErr),
+    /// The record stream couldn't be created, connected or read.
+    Stream(PAErr),
+    /// The server went away after the context was ready.
+    Disconnected,
+    /// The requested sample spec or channel map can't be used.
+    InvalidSpec(String),
+    /// Drawing to or reading from the terminal failed.
+    Terminal(io::Error),
+    /// Writing an output file failed.
+    Output(PathBuf, io::Error),
+    /// Reading an input file failed or it isn't in a format we can read.
+    Input(PathBuf, io::Error),
+    /// The configuration file has a setting we can't use.
+    Config(PathBuf, String),
+}
+
+impl Error {
+    /// The process exit code for this error, distinct per variant so
+    /// supervisors can tell failures apart. Command line errors exit with
+    /// 2 and panics with 101, and 1 is left unused.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::Connection(_) => 3,
+            Error::NoDevice(_) => 4,
+            Error::Introspection(_) => 5,
+            Error::Stream(_) => 6,
+            Error::Disconnected => 7,
+            Error::InvalidSpec(_) => 8,
+            Error::Terminal(_) => 9,
+            Error::Output(..) => 10,
+            Error::Input(..) => 11,
+            Error::Config(..) => 12,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Connection(err) => write!(f, "Could not connect to the sound server: {}.", err),
+            Error::NoDevice(err) => write!(f, "{}", err),
+            Error::Introspection(err) => write!(f, "Could not query the sound server: {}.", err),
+            Error::Stream(err) => write!(f, "Record stream failed: {}.", err),
+            Error::Disconnected => write!(f, "Lost connection to the sound server."),
+            Error::InvalidSpec(reason) => write!(f, "Invalid sample spec: {}.", reason),
+            Error::Terminal(err) => write!(f, "Terminal error: {}.", err),
+            Error::Output(path, err) => write!(f, "Could not write {}: {}.", path.display(), err),
+            Error::Input(path, err) => write!(f, "Could not read {}: {}.", path.display(), err),
+            Error::Config(path, reason) => {
+                write!(
+                    f,
+                    "Invalid configuration in {}: {}.",
+                    path.display(),
+                    reason
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Connection(err) | Error::Introspection(err) | Error::Stream(err) => Some(err),
+            Error::NoDevice(err) => Some(err),
+            Error::Terminal(err) | Error::Output(_, err) | Error::Input(_, err) => Some(err),
+            Error::Disconnected | Error::InvalidSpec(_) | Error::Config(..) => None,
+        }
+    }
+}
+
+impl From<SelectError> for Error {
+    fn from(err: SelectError) -> Self {
+        Error::NoDevice(err)
+    }
+}
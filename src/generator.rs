@@ -0,0 +1,302 @@
+use std::{f64::consts::TAU, sync::mpsc::RecvTimeoutError, time::Duration};
+
+use clap::ValueEnum;
+use pulse::{
+    channelmap::{Map, Position},
+    sample::{Format, Spec},
+};
+
+use crate::{
+    capture::CaptureEvent,
+    error::Error,
+    logging,
+    source::{AudioSource, Pacer},
+    spec::SpecRequest,
+};
+
+/// Format used for whatever the request leaves unset.
+const DEFAULT_SPEC: Spec = Spec {
+    format: Format::F32le,
+    channels: 2,
+    rate: 48000,
+};
+
+/// Audio generated per event.
+const CHUNK_DURATION: Duration = Duration::from_millis(10);
+
+/// Seed of the noise generator, fixed so every run produces the same noise.
+const NOISE_SEED: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// Test signals the generator can produce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Signal {
+    Sine,
+    Square,
+    Saw,
+    /// Uniform white noise.
+    WhiteNoise,
+    /// Noise falling off at 3 dB per octave.
+    PinkNoise,
+    /// A logarithmic sine sweep from --frequency to --end-frequency,
+    /// repeated.
+    Sweep,
+    /// Single sample impulses, --frequency times a second.
+    Impulse,
+    /// Sines at each of --tones, mixed at equal levels.
+    MultiTone,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratorConfig {
+    pub signal: Signal,
+    /// Frequency of periodic signals, start of sweeps and rate of impulses,
+    /// in Hz.
+    pub frequency: f64,
+    /// Frequency sweeps end at, in Hz.
+    pub end_frequency: f64,
+    pub sweep_duration: Duration,
+    /// Frequencies of the multi-tone signal, in Hz.
+    pub tones: Vec<f64>,
+    /// Peak level in dBFS.
+    pub amplitude: f32,
+    /// Channels that carry the signal, all of them if empty. The others are
+    /// silent.
+    pub route: Vec<Position>,
+    /// Resolved against 48 kHz F32LE stereo.
+    pub spec: SpecRequest,
+}
+
+/// Produces one of the [`Signal`]s, one sample at a time, in -1.0..=1.0.
+struct Oscillator {
+    signal: Signal,
+    rate: f64,
+    /// Frequencies of the periodic signals, a single one except for
+    /// multi-tone.
+    frequencies: Vec<f64>,
+    /// Position in the current period of each frequency, in 0.0..1.0.
+    phases: Vec<f64>,
+    end_frequency: f64,
+    sweep_len: u64,
+    /// Samples produced so far.
+    position: u64,
+    noise: u64,
+    /// State of the pink noise filter.
+    pink: [f64; 7],
+}
+
+impl Oscillator {
+    fn new(config: &GeneratorConfig, rate: u32) -> Self {
+        let frequencies = match config.signal {
+            Signal::MultiTone => config.tones.clone(),
+            _ => vec![config.frequency],
+        };
+        Oscillator {
+            signal: config.signal,
+            rate: rate as f64,
+            phases: vec![0.0; frequencies.len()],
+            frequencies,
+            end_frequency: config.end_frequency,
+            sweep_len: ((config.sweep_duration.as_secs_f64() * rate as f64) as u64).max(1),
+            position: 0,
+            noise: NOISE_SEED,
+            pink: [0.0; 7],
+        }
+    }
+
+    fn next(&mut self) -> f64 {
+        let value = match self.signal {
+            Signal::Sine => (TAU * self.phases[0]).sin(),
+            Signal::Square => {
+                if self.phases[0] < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Signal::Saw => 2.0 * self.phases[0] - 1.0,
+            Signal::WhiteNoise => self.white(),
+            Signal::PinkNoise => self.pink(),
+            Signal::Sweep => self.sweep(),
+            Signal::Impulse => {
+                let period = (self.rate / self.frequencies[0]).round().max(1.0) as u64;
+                if self.position.is_multiple_of(period) {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Signal::MultiTone => {
+                let sum: f64 = self.phases.iter().map(|phase| (TAU * phase).sin()).sum();
+                sum / self.phases.len().max(1) as f64
+            }
+        };
+
+        for (phase, frequency) in self.phases.iter_mut().zip(&self.frequencies) {
+            *phase = (*phase + frequency / self.rate).fract();
+        }
+        self.position += 1;
+        value
+    }
+
+    /// Uniform noise from xorshift64*.
+    fn white(&mut self) -> f64 {
+        self.noise ^= self.noise >> 12;
+        self.noise ^= self.noise << 25;
+        self.noise ^= self.noise >> 27;
+        let bits = self.noise.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11;
+        bits as f64 / (1u64 << 52) as f64 - 1.0
+    }
+
+    /// White noise through Paul Kellet's pink filter.
+    fn pink(&mut self) -> f64 {
+        let white = self.white();
+        let b = &mut self.pink;
+        b[0] = 0.99886 * b[0] + white * 0.0555179;
+        b[1] = 0.99332 * b[1] + white * 0.0750759;
+        b[2] = 0.96900 * b[2] + white * 0.1538520;
+        b[3] = 0.86650 * b[3] + white * 0.3104856;
+        b[4] = 0.55000 * b[4] + white * 0.5329522;
+        b[5] = -0.7616 * b[5] - white * 0.0168980;
+        let pink = b.iter().sum::<f64>() + white * 0.5362;
+        b[6] = white * 0.115926;
+        (pink * 0.11).clamp(-1.0, 1.0)
+    }
+
+    /// An exponential sine sweep, whose frequency rises by the same number
+    /// of octaves in equal times.
+    fn sweep(&self) -> f64 {
+        let start = self.frequencies[0];
+        let growth = (self.end_frequency / start).ln();
+        let duration = self.sweep_len as f64 / self.rate;
+        let t = (self.position % self.sweep_len) as f64 / self.rate;
+        let cycles = if growth.abs() < f64::EPSILON {
+            start * t
+        } else {
+            start * duration / growth * ((t * growth / duration).exp() - 1.0)
+        };
+        (TAU * cycles.fract()).sin()
+    }
+}
+
+/// A synthetic signal standing in for the record stream, delivered in real
+/// time.
+pub struct GeneratorSource {
+    oscillator: Oscillator,
+    signal: Signal,
+    spec: Spec,
+    map: Map,
+    /// Whether each channel carries the signal.
+    routed: Vec<bool>,
+    gain: f64,
+    pacer: Option<Pacer>,
+    chunk_frames: usize,
+}
+
+impl GeneratorSource {
+    pub fn new(config: GeneratorConfig) -> Result<Self, Error> {
+        let mut stereo = Map::default();
+        stereo.init_stereo();
+        let (spec, map) = config.spec.resolve(&DEFAULT_SPEC, &stereo)?;
+
+        let nyquist = spec.rate as f64 / 2.0;
+        let frequencies = match config.signal {
+            Signal::WhiteNoise | Signal::PinkNoise => Vec::new(),
+            Signal::Sweep => vec![config.frequency, config.end_frequency],
+            Signal::MultiTone => config.tones.clone(),
+            _ => vec![config.frequency],
+        };
+        if let Some(frequency) = frequencies
+            .into_iter()
+            .find(|&frequency| !(frequency > 0.0 && frequency < nyquist))
+        {
+            return Err(Error::InvalidSpec(format!(
+                "the test signal's {} Hz have to be above 0 and below half the \
+                 sample rate of {} Hz",
+                frequency, spec.rate
+            )));
+        }
+
+        if let Some(&missing) = config
+            .route
+            .iter()
+            .find(|&&position| !map.has_position(position))
+        {
+            return Err(Error::InvalidSpec(format!(
+                "channel map {} has no {} channel",
+                map.print(),
+                Position::to_string(missing).unwrap_or_default()
+            )));
+        }
+
+        Ok(GeneratorSource {
+            oscillator: Oscillator::new(&config, spec.rate),
+            signal: config.signal,
+            routed: map
+                .get()
+                .iter()
+                .map(|position| config.route.is_empty() || config.route.contains(position))
+                .collect(),
+            spec,
+            map,
+            gain: 10f64.powf(config.amplitude as f64 / 20.0),
+            pacer: None,
+            chunk_frames: (spec.rate as u128 * CHUNK_DURATION.as_millis() / 1000).max(1) as usize,
+        })
+    }
+}
+
+impl AudioSource for GeneratorSource {
+    fn recv_timeout(&mut self, timeout: Duration) -> Result<CaptureEvent, RecvTimeoutError> {
+        let Some(pacer) = &mut self.pacer else {
+            self.pacer = Some(Pacer::start(self.spec));
+            let name = self.signal.to_possible_value().unwrap();
+            log::info!(
+                target: logging::STREAM,
+                "Generating {} in {}.",
+                name.get_name(),
+                self.spec.print()
+            );
+            return Ok(CaptureEvent::Ready {
+                sink: name.get_name().to_string(),
+                source: "generator".to_string(),
+                spec: self.spec,
+                channel_map: self.map,
+            });
+        };
+
+        if !pacer.wait(timeout) {
+            return Err(RecvTimeoutError::Timeout);
+        }
+
+        let mut data = Vec::with_capacity(self.chunk_frames * self.spec.frame_size());
+        for _ in 0..self.chunk_frames {
+            let sample = self.oscillator.next() * self.gain;
+            for &routed in &self.routed {
+                encode(
+                    self.spec.format,
+                    if routed { sample } else { 0.0 },
+                    &mut data,
+                );
+            }
+        }
+        pacer.delivered(data.len());
+
+        Ok(CaptureEvent::Data(data))
+    }
+}
+
+/// Appends `sample`, in -1.0..=1.0, to `data` in `format`, which must be one
+/// the decoder supports.
+fn encode(format: Format, sample: f64, data: &mut Vec<u8>) {
+    let sample = sample.clamp(-1.0, 1.0);
+    let scaled = |max: f64| (sample * max).round().min(max - 1.0) as i32;
+    match format {
+        Format::U8 => data.push((scaled(128.0) + 128) as u8),
+        Format::S16le => data.extend_from_slice(&(scaled(32768.0) as i16).to_le_bytes()),
+        Format::S24le => data.extend_from_slice(&scaled(8388608.0).to_le_bytes()[..3]),
+        Format::S32le => data.extend_from_slice(
+            &((sample * 2147483648.0).round().min(2147483647.0) as i32).to_le_bytes(),
+        ),
+        _ => data.extend_from_slice(&(sample as f32).to_le_bytes()),
+    }
+}
@@ -0,0 +1,26 @@
+//! Captures the audio playing on a PulseAudio sink, analyzes it and
+//! renders it to the terminal.
+//!
+//! The pipeline is a [`source::AudioSource`], the live [`capture::Capture`]
+//! or a file or test signal, whose bytes a [`decode::Decoder`] turns into
+//! [`decode::AudioBlock`]s for the measurements in [`analysis`], which the
+//! views in [`render`] draw.
+
+pub mod analysis;
+pub mod capture;
+pub mod connection;
+pub mod decode;
+pub mod device;
+pub mod error;
+pub mod generator;
+pub mod list;
+pub mod logging;
+pub mod record;
+pub mod reload;
+pub mod render;
+pub mod server;
+pub mod source;
+pub mod spec;
+pub mod stop;
+pub mod visualize;
+pub mod wav;
@@ -0,0 +1,184 @@
+use serde::Serialize;
+
+use crate::{
+    connection::Connection,
+    device::{ServerDefaults, Sink, Source},
+    error::Error,
+    server::Server,
+};
+
+/// Prints the sinks and sources of the server as a table, or as JSON if
+/// `json` is set.
+pub fn list_devices(json: bool) -> Result<(), Error> {
+    let connection = Connection::connect()?;
+
+    let sinks = connection.sinks()?;
+    let sources = connection.sources()?;
+    let defaults = connection.server_defaults()?;
+    connection.disconnect();
+
+    let list = DeviceList::new(sinks, sources, defaults);
+    if json {
+        println!("{}", list.to_json().unwrap());
+    } else {
+        print!("{}", list.to_table());
+    }
+
+    Ok(())
+}
+
+/// Everything `list-devices` reports, in the shape of its `--json` output.
+#[derive(Debug, Serialize)]
+pub struct DeviceList {
+    pub default_sink: Option<String>,
+    pub default_source: Option<String>,
+    pub sinks: Vec<SinkEntry>,
+    pub sources: Vec<SourceEntry>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SinkEntry {
+    #[serde(flatten)]
+    pub sink: Sink,
+    pub is_default: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SourceEntry {
+    #[serde(flatten)]
+    pub source: Source,
+    pub is_default: bool,
+}
+
+impl DeviceList {
+    pub fn new(sinks: Vec<Sink>, sources: Vec<Source>, defaults: ServerDefaults) -> Self {
+        let sinks = sinks
+            .into_iter()
+            .map(|sink| SinkEntry {
+                is_default: defaults.sink.as_deref() == Some(sink.name.as_str()),
+                sink,
+            })
+            .collect();
+        let sources = sources
+            .into_iter()
+            .map(|source| SourceEntry {
+                is_default: defaults.source.as_deref() == Some(source.name.as_str()),
+                source,
+            })
+            .collect();
+
+        DeviceList {
+            default_sink: defaults.sink,
+            default_source: defaults.source,
+            sinks,
+            sources,
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Renders sinks and sources as aligned tables. The default device of
+    /// each kind is marked with `*`.
+    pub fn to_table(&self) -> String {
+        let sinks: Vec<Row> = self
+            .sinks
+            .iter()
+            .map(|entry| Row {
+                is_default: entry.is_default,
+                index: entry.sink.index,
+                name: &entry.sink.name,
+                description: &entry.sink.description,
+                spec: entry.sink.sample_spec.to_string(),
+                channels: entry.sink.channel_map.join(","),
+                monitor: entry.sink.monitor_source_name.as_deref().unwrap_or("-"),
+            })
+            .collect();
+        let sources: Vec<Row> = self
+            .sources
+            .iter()
+            .map(|entry| Row {
+                is_default: entry.is_default,
+                index: entry.source.index,
+                name: &entry.source.name,
+                description: &entry.source.description,
+                spec: entry.source.sample_spec.to_string(),
+                channels: entry.source.channel_map.join(","),
+                monitor: entry.source.monitor_of_sink_name.as_deref().unwrap_or("-"),
+            })
+            .collect();
+
+        let mut out = String::new();
+        out.push_str("Sinks:\n");
+        write_table(&mut out, &sinks, "MONITOR SOURCE");
+        out.push_str("\nSources:\n");
+        write_table(&mut out, &sources, "MONITOR OF SINK");
+        out
+    }
+}
+
+struct Row<'a> {
+    is_default: bool,
+    index: u32,
+    name: &'a str,
+    description: &'a str,
+    spec: String,
+    channels: String,
+    monitor: &'a str,
+}
+
+fn write_table(out: &mut String, rows: &[Row], monitor_header: &str) {
+    if rows.is_empty() {
+        out.push_str("  (none)\n");
+        return;
+    }
+
+    let header = ["INDEX", "NAME", "DESCRIPTION", "SPEC", "CHANNEL MAP"];
+    let widths = [
+        column_width(header[0], rows.iter().map(|r| r.index.to_string().len())),
+        column_width(header[1], rows.iter().map(|r| r.name.chars().count())),
+        column_width(
+            header[2],
+            rows.iter().map(|r| r.description.chars().count()),
+        ),
+        column_width(header[3], rows.iter().map(|r| r.spec.len())),
+        column_width(header[4], rows.iter().map(|r| r.channels.len())),
+    ];
+
+    out.push_str(&format!(
+        "    {:>w0$}  {:w1$}  {:w2$}  {:w3$}  {:w4$}  {}\n",
+        header[0],
+        header[1],
+        header[2],
+        header[3],
+        header[4],
+        monitor_header,
+        w0 = widths[0],
+        w1 = widths[1],
+        w2 = widths[2],
+        w3 = widths[3],
+        w4 = widths[4],
+    ));
+    for row in rows {
+        out.push_str(&format!(
+            "  {} {:>w0$}  {:w1$}  {:w2$}  {:w3$}  {:w4$}  {}\n",
+            if row.is_default { '*' } else { ' ' },
+            row.index,
+            row.name,
+            row.description,
+            row.spec,
+            row.channels,
+            row.monitor,
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2],
+            w3 = widths[3],
+            w4 = widths[4],
+        ));
+    }
+}
+
+fn column_width(header: &str, lengths: impl Iterator<Item = usize>) -> usize {
+    lengths.fold(header.len(), usize::max)
+}
@@ -0,0 +1,221 @@
+//! Diagnostics through the `log` facade.
+//!
+//! Records are filtered by level per target, one target per subsystem, and
+//! written to stderr as text and optionally to a file as JSON lines. While
+//! the terminal views are up, stderr output is held back and written once
+//! the terminal is restored, so it never ends up in the middle of a frame.
+
+use std::{
+    collections::VecDeque,
+    fs::{File, OpenOptions},
+    io::{self, LineWriter, Write},
+    path::PathBuf,
+    str::FromStr,
+    sync::{Mutex, OnceLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+use crate::error::Error;
+
+/// Connecting to the sound server and the context's state.
+pub const CONTEXT: &str = "context";
+/// Querying devices and server defaults.
+pub const INTROSPECT: &str = "introspect";
+/// Record streams and the sources standing in for them.
+pub const STREAM: &str = "stream";
+/// Decoding and analyzing audio.
+pub const ANALYSIS: &str = "analysis";
+/// The terminal and the frames drawn to it.
+pub const RENDER: &str = "render";
+/// The configuration file.
+pub const CONFIG: &str = "config";
+
+/// How many held back lines are kept; older ones are dropped first.
+const MAX_HELD_LINES: usize = 1000;
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+/// Which records to keep: a level for every target, and optionally
+/// different levels for some of them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogFilter {
+    pub default: LevelFilter,
+    pub targets: Vec<(String, LevelFilter)>,
+}
+
+impl LogFilter {
+    /// The level of `target`, whose own level wins over the default.
+    pub fn level(&self, target: &str) -> LevelFilter {
+        self.targets
+            .iter()
+            .rev()
+            .find(|(name, _)| name == target)
+            .map_or(self.default, |&(_, level)| level)
+    }
+
+    /// The most verbose level of any target.
+    fn max_level(&self) -> LevelFilter {
+        self.targets
+            .iter()
+            .map(|&(_, level)| level)
+            .fold(self.default, Ord::max)
+    }
+}
+
+impl Default for LogFilter {
+    fn default() -> Self {
+        LogFilter {
+            default: LevelFilter::Warn,
+            targets: Vec::new(),
+        }
+    }
+}
+
+/// Parses comma separated levels like `warn,stream=debug`: a bare level
+/// sets the default, `target=level` the level of one target.
+impl FromStr for LogFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let level = |level: &str| {
+            level
+                .parse::<LevelFilter>()
+                .map_err(|_| format!("unknown log level `{}`", level))
+        };
+
+        let mut filter = LogFilter::default();
+        for part in s.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+            match part.split_once('=') {
+                Some((target, part)) => filter.targets.push((target.to_string(), level(part)?)),
+                None => filter.default = level(part)?,
+            }
+        }
+
+        Ok(filter)
+    }
+}
+
+/// Where diagnostics go.
+#[derive(Debug, Clone, Default)]
+pub struct LogConfig {
+    pub filter: LogFilter,
+    /// Also append every record to this file as a JSON object per line.
+    pub file: Option<PathBuf>,
+}
+
+/// Installs the logger. Only the first call has an effect.
+pub fn init(config: LogConfig) -> Result<(), Error> {
+    let file = match config.file {
+        Some(path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .map_err(|err| Error::Output(path, err))?;
+            Some(Mutex::new(LineWriter::new(file)))
+        }
+        None => None,
+    };
+
+    let max_level = config.filter.max_level();
+    let logger = LOGGER.get_or_init(|| Logger {
+        filter: config.filter,
+        stderr: Mutex::new(Stderr::default()),
+        file,
+    });
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(max_level);
+    }
+
+    Ok(())
+}
+
+/// Holds back stderr output until [`release_stderr`], while something
+/// else owns the terminal.
+pub fn hold_stderr() {
+    if let Some(logger) = LOGGER.get() {
+        logger.stderr.lock().unwrap().held = true;
+    }
+}
+
+/// Writes the stderr output held back since [`hold_stderr`], and writes
+/// further output right away again.
+pub fn release_stderr() {
+    let Some(logger) = LOGGER.get() else {
+        return;
+    };
+
+    let mut stderr = logger.stderr.lock().unwrap();
+    stderr.held = false;
+    let mut out = io::stderr().lock();
+    if stderr.dropped > 0 {
+        let _ = writeln!(out, "[{} earlier log lines dropped]", stderr.dropped);
+        stderr.dropped = 0;
+    }
+    for line in stderr.backlog.drain(..) {
+        let _ = writeln!(out, "{}", line);
+    }
+}
+
+#[derive(Default)]
+struct Stderr {
+    held: bool,
+    backlog: VecDeque<String>,
+    /// Lines that didn't fit into the backlog.
+    dropped: usize,
+}
+
+struct Logger {
+    filter: LogFilter,
+    stderr: Mutex<Stderr>,
+    file: Option<Mutex<LineWriter<File>>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filter.level(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = format!("[{} {}] {}", record.level(), record.target(), record.args());
+        {
+            let mut stderr = self.stderr.lock().unwrap();
+            if stderr.held {
+                if stderr.backlog.len() == MAX_HELD_LINES {
+                    stderr.backlog.pop_front();
+                    stderr.dropped += 1;
+                }
+                stderr.backlog.push_back(line);
+            } else {
+                let _ = writeln!(io::stderr().lock(), "{}", line);
+            }
+        }
+
+        if let Some(file) = &self.file {
+            let time = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0.0, |time| time.as_secs_f64());
+            let object = serde_json::json!({
+                "time": time,
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            });
+            let mut file = file.lock().unwrap();
+            let _ = serde_json::to_writer(&mut *file, &object);
+            let _ = writeln!(file);
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            let _ = file.lock().unwrap().flush();
+        }
+    }
+}
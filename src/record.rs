@@ -0,0 +1,244 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::RecvTimeoutError,
+    time::Duration,
+};
+
+use pulse::{
+    channelmap::Map,
+    sample::{Format, Spec},
+};
+
+use crate::{
+    capture::CaptureEvent,
+    decode::Decoder,
+    error::Error,
+    logging,
+    source::{AudioSource, SourceConfig},
+    stop::{StopConditions, StopConfig, StopReason},
+    wav::{WavWriter, MAX_DATA_LEN},
+};
+
+/// How often recording checks its stop conditions when no audio arrives.
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Records the audio from `config` to `output` until a stop condition is
+/// met or the source ends, starting a new file every `max_duration` if
+/// given.
+pub fn record(
+    config: SourceConfig,
+    output: PathBuf,
+    max_duration: Option<Duration>,
+    stop: StopConfig,
+) -> Result<(), Error> {
+    // The headers can only be completed when the recording ends, so signals
+    // stop it instead of killing the process.
+    let mut stop = StopConditions::start(stop);
+    let mut recorder = Recorder::new(output, max_duration);
+    let mut source = config.open()?;
+    let result = record_events(source.as_mut(), &mut recorder, &mut stop);
+    drop(source);
+
+    recorder.finish()?;
+    if let Some(reason) = result? {
+        log::info!(target: logging::STREAM, "{}", reason);
+    }
+
+    Ok(())
+}
+
+/// Writes audio from `source` to `recorder` until a stop condition is met
+/// or the source ends. Returns the condition that was met.
+fn record_events(
+    source: &mut dyn AudioSource,
+    recorder: &mut Recorder,
+    stop: &mut StopConditions,
+) -> Result<Option<StopReason>, Error> {
+    // Audio is only decoded to look for silence.
+    let mut decoder = None;
+
+    loop {
+        if let Some(reason) = stop.reason() {
+            return Ok(Some(reason));
+        }
+
+        let timeout = stop.remaining().map_or(STOP_POLL_INTERVAL, |remaining| {
+            remaining.min(STOP_POLL_INTERVAL)
+        });
+        match source.recv_timeout(timeout) {
+            Ok(CaptureEvent::Ready {
+                sink,
+                spec,
+                channel_map,
+                ..
+            }) => {
+                if let Some(path) = recorder.start(&spec, &channel_map)? {
+                    log::info!(
+                        target: logging::STREAM,
+                        "Recording {} ({}) from {} to {}.",
+                        spec.print(),
+                        channel_map.print(),
+                        sink,
+                        path.display()
+                    );
+                }
+                decoder = Some(Decoder::new(&spec, &channel_map)?);
+            }
+            // Logged by the capture thread.
+            Ok(
+                CaptureEvent::Switched { .. }
+                | CaptureEvent::Rejected(_)
+                | CaptureEvent::Reconnecting { .. },
+            ) => {}
+            Ok(CaptureEvent::Data(data)) => {
+                recorder.write(&data)?;
+                if let Some(decoder) = &mut decoder {
+                    stop.push(&decoder.decode(&data));
+                }
+            }
+            Ok(CaptureEvent::Hole(len)) => {
+                recorder.write_silence(len)?;
+                if let Some(decoder) = &mut decoder {
+                    stop.push(&decoder.decode_hole(len));
+                }
+            }
+            Ok(CaptureEvent::Failed(err)) => return Err(err),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return Ok(None),
+        }
+    }
+}
+
+/// Writes a record stream to WAV files, starting a new file when the
+/// current one reaches the maximum duration, when it would outgrow what
+/// RIFF can describe, and when the stream's spec changes after a
+/// reconnect.
+pub struct Recorder {
+    path: PathBuf,
+    max_duration: Option<Duration>,
+    /// Number of the next file when splitting.
+    next_part: u32,
+    current: Option<Current>,
+}
+
+struct Current {
+    writer: WavWriter,
+    spec: Spec,
+    map: Map,
+    /// Data bytes after which the next file is started, a whole number of
+    /// frames.
+    max_len: u64,
+}
+
+impl Recorder {
+    /// Records to `path`, or with a `max_duration`, to files numbered like
+    /// `name-001.wav` next to it.
+    pub fn new(path: PathBuf, max_duration: Option<Duration>) -> Self {
+        Recorder {
+            path,
+            max_duration,
+            next_part: 1,
+            current: None,
+        }
+    }
+
+    /// Prepares for audio in `spec` laid out as `map`. Starts a new file
+    /// unless the current one already has that format. Returns the path of
+    /// a newly started file.
+    pub fn start(&mut self, spec: &Spec, map: &Map) -> Result<Option<&Path>, Error> {
+        if let Some(current) = &self.current {
+            if current.spec == *spec && current.map.get() == map.get() {
+                return Ok(None);
+            }
+        }
+
+        self.open(*spec, *map)?;
+        Ok(self.current.as_ref().map(|current| current.writer.path()))
+    }
+
+    fn open(&mut self, spec: Spec, map: Map) -> Result<(), Error> {
+        self.finish()?;
+
+        let path = match self.max_duration {
+            Some(_) => numbered(&self.path, self.next_part),
+            None if self.next_part > 1 => numbered(&self.path, self.next_part),
+            None => self.path.clone(),
+        };
+        self.next_part += 1;
+
+        let frame_size = spec.frame_size() as u64;
+        let max_len = self
+            .max_duration
+            .map_or(MAX_DATA_LEN, |duration| {
+                (duration.as_secs_f64() * spec.rate as f64) as u64 * frame_size
+            })
+            .clamp(frame_size, MAX_DATA_LEN);
+
+        self.current = Some(Current {
+            writer: WavWriter::create(&path, &spec, &map)?,
+            spec,
+            map,
+            max_len: max_len - max_len % frame_size,
+        });
+        Ok(())
+    }
+
+    /// Appends `data`, splitting into a new file when the current one is
+    /// full. Data before the first [`start`](Recorder::start) is dropped.
+    pub fn write(&mut self, mut data: &[u8]) -> Result<(), Error> {
+        while !data.is_empty() {
+            let Some(current) = &mut self.current else {
+                return Ok(());
+            };
+
+            let room = current.max_len - current.writer.data_len();
+            if room == 0 {
+                let (spec, map) = (current.spec, current.map);
+                self.open(spec, map)?;
+                continue;
+            }
+
+            let len = (room.min(data.len() as u64)) as usize;
+            current.writer.write(&data[..len])?;
+            data = &data[len..];
+        }
+
+        Ok(())
+    }
+
+    /// Writes `len` bytes of silence for a hole in the stream, so the
+    /// recording keeps its timing.
+    pub fn write_silence(&mut self, len: usize) -> Result<(), Error> {
+        let Some(current) = &self.current else {
+            return Ok(());
+        };
+
+        let silence = if current.spec.format == Format::U8 {
+            0x80
+        } else {
+            0
+        };
+        self.write(&vec![silence; len])
+    }
+
+    /// Finishes the current file, if any.
+    pub fn finish(&mut self) -> Result<(), Error> {
+        match self.current.take() {
+            Some(current) => current.writer.finish(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// `path` with `-NNN` appended to the file stem.
+fn numbered(path: &Path, part: u32) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = match path.extension() {
+        Some(extension) => format!("{}-{:03}.{}", stem, part, extension.to_string_lossy()),
+        None => format!("{}-{:03}", stem, part),
+    };
+    path.with_file_name(name)
+}
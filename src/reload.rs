@@ -0,0 +1,88 @@
+//! Picking up a changed settings file while the visualizer runs.
+//!
+//! Gain, smoothing and colors change in place, and a view only starts
+//! over when the analysis behind it is set up differently. A different
+//! device or sample spec restarts the record stream on the same
+//! connection, and only a different kind of source is opened anew. The
+//! loudness measurement, stop conditions and output files keep the
+//! settings they started with.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use inotify::{Inotify, WatchMask};
+
+use crate::{analysis::AnalysisConfig, error::Error, render::DisplayConfig, source::SourceConfig};
+
+/// Everything the visualizer can change while running.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub source: SourceConfig,
+    pub analysis: AnalysisConfig,
+    pub display: DisplayConfig,
+}
+
+/// Loads the settings again whenever their file is written.
+pub struct Reload {
+    path: PathBuf,
+    inotify: Inotify,
+    buffer: Vec<u8>,
+    load: Box<dyn FnMut() -> Result<Settings, Error>>,
+}
+
+impl Reload {
+    /// Watches `path`, calling `load` after every change. The directory is
+    /// watched rather than the file, so editors that save by replacing the
+    /// file are noticed too.
+    pub fn watch(
+        path: PathBuf,
+        load: impl FnMut() -> Result<Settings, Error> + 'static,
+    ) -> Result<Reload, Error> {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+
+        let watch = || {
+            let inotify = Inotify::init()?;
+            inotify
+                .watches()
+                .add(dir, WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO)?;
+            Ok(inotify)
+        };
+        let inotify = watch().map_err(|err| Error::Input(path.clone(), err))?;
+
+        Ok(Reload {
+            path,
+            inotify,
+            buffer: vec![0; 4096],
+            load: Box::new(load),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The settings as of the latest change, if the file changed since the
+    /// last call. Never blocks.
+    pub fn poll(&mut self) -> Option<Result<Settings, Error>> {
+        let name = self.path.file_name();
+        let mut changed = false;
+        loop {
+            match self.inotify.read_events(&mut self.buffer) {
+                Ok(events) => {
+                    changed |= events
+                        .into_iter()
+                        .any(|event| event.name.is_some_and(|event| Some(event) == name));
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => return Some(Err(Error::Input(self.path.clone(), err))),
+            }
+        }
+
+        changed.then(|| (self.load)())
+    }
+}
@@ -0,0 +1,58 @@
+use crate::render::{
+    color::Gradient,
+    screen::{Cell, Rect, Screen},
+};
+
+/// Block elements from empty to full in eighths of a cell.
+const BLOCKS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Draws `levels`, each in 0.0..=1.0, as vertical bars filling `area`.
+///
+/// Bars share the width evenly, with a one cell gap once they are wide
+/// enough, and are centered if the width doesn't divide evenly. With more
+/// levels than columns, each column shows the highest of the levels it
+/// covers. Bars are colored by height with `gradient`.
+pub fn draw(screen: &mut Screen, area: Rect, levels: &[f32], gradient: &Gradient) {
+    if levels.is_empty() || area.width == 0 || area.height == 0 {
+        return;
+    }
+
+    let columns = area.width as usize;
+    let levels: Vec<f32> = if levels.len() > columns {
+        (0..columns)
+            .map(|column| {
+                let start = column * levels.len() / columns;
+                let end = ((column + 1) * levels.len() / columns).max(start + 1);
+                levels[start..end].iter().copied().fold(0.0, f32::max)
+            })
+            .collect()
+    } else {
+        levels.to_vec()
+    };
+
+    let slot = columns / levels.len();
+    let gap = usize::from(slot >= 3);
+    let offset = (columns - slot * levels.len()) / 2;
+
+    for (index, &level) in levels.iter().enumerate() {
+        let eighths = (level.clamp(0.0, 1.0) * area.height as f32 * 8.0).round() as usize;
+        let x = area.x as usize + offset + index * slot;
+
+        for row in 0..area.height {
+            let filled = eighths.saturating_sub(row as usize * 8).min(8);
+            if filled == 0 {
+                break;
+            }
+
+            let cell = Cell {
+                symbol: BLOCKS[filled],
+                color: Some(gradient.at((row as f32 + 0.5) / area.height as f32)),
+                background: None,
+            };
+            let y = area.y + area.height - 1 - row;
+            for column in x..x + slot - gap {
+                screen.set(column as u16, y, cell);
+            }
+        }
+    }
+}
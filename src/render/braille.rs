@@ -0,0 +1,102 @@
+use crate::render::{
+    color::Rgb,
+    screen::{Cell, Rect, Screen},
+};
+
+/// Bit of each dot in a braille character, by row and then column.
+const DOTS: [[u8; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+/// A canvas of braille dots, 2x4 per cell, for plots with more resolution
+/// than whole cells. A cell takes the color of the last dot set in it,
+/// blended with the previous one if they differ.
+pub struct BrailleCanvas {
+    width: u16,
+    height: u16,
+    dots: Vec<u8>,
+    colors: Vec<Option<Rgb>>,
+}
+
+impl BrailleCanvas {
+    /// A blank canvas covering `width` by `height` cells.
+    pub fn new(width: u16, height: u16) -> Self {
+        let cells = width as usize * height as usize;
+        BrailleCanvas {
+            width,
+            height,
+            dots: vec![0; cells],
+            colors: vec![None; cells],
+        }
+    }
+
+    /// Width in dots.
+    pub fn dot_width(&self) -> usize {
+        self.width as usize * 2
+    }
+
+    /// Height in dots.
+    pub fn dot_height(&self) -> usize {
+        self.height as usize * 4
+    }
+
+    /// Sets the dot at `x`, `y`. Dots outside the canvas are ignored.
+    pub fn set(&mut self, x: usize, y: usize, color: Rgb) {
+        if x >= self.dot_width() || y >= self.dot_height() {
+            return;
+        }
+
+        let cell = y / 4 * self.width as usize + x / 2;
+        self.dots[cell] |= DOTS[y % 4][x % 2];
+        self.colors[cell] = Some(match self.colors[cell] {
+            Some(previous) if previous != color => previous.mix(color, 0.5),
+            _ => color,
+        });
+    }
+
+    /// Sets every dot on the line from `from` to `to`.
+    pub fn line(&mut self, from: (usize, usize), to: (usize, usize), color: Rgb) {
+        let (mut x, mut y) = (from.0 as isize, from.1 as isize);
+        let (x1, y1) = (to.0 as isize, to.1 as isize);
+        let (dx, dy) = ((x1 - x).abs(), -(y1 - y).abs());
+        let (step_x, step_y) = ((x1 - x).signum(), (y1 - y).signum());
+        let mut error = dx + dy;
+
+        loop {
+            self.set(x as usize, y as usize, color);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    /// Copies the cells with at least one dot set into `area` of `screen`.
+    pub fn draw(&self, screen: &mut Screen, area: Rect) {
+        for row in 0..self.height.min(area.height) {
+            for column in 0..self.width.min(area.width) {
+                let index = row as usize * self.width as usize + column as usize;
+                if self.dots[index] == 0 {
+                    continue;
+                }
+
+                let symbol = char::from_u32(0x2800 + self.dots[index] as u32).unwrap_or(' ');
+                screen.set(
+                    area.x + column,
+                    area.y + row,
+                    Cell {
+                        symbol,
+                        color: self.colors[index],
+                        background: None,
+                    },
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use clap::ValueEnum;
+use crossterm::style::Color;
+
+/// A color in 24 bit RGB. Views only deal in these; the terminal converts
+/// them to whatever the [`ColorMode`] supports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    /// Linear interpolation from `self` to `other` by `t` in 0.0..=1.0.
+    pub fn mix(self, other: Rgb, t: f32) -> Rgb {
+        let channel = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        Rgb(
+            channel(self.0, other.0),
+            channel(self.1, other.1),
+            channel(self.2, other.2),
+        )
+    }
+}
+
+/// Which colors the terminal is sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorMode {
+    /// Truecolor if `COLORTERM` says so, 256 colors otherwise.
+    Auto,
+    /// 24 bit RGB escape codes.
+    Truecolor,
+    /// The xterm 256 color palette.
+    Ansi256,
+    /// No colors at all.
+    None,
+}
+
+impl ColorMode {
+    /// Resolves `Auto` to the mode the terminal supports.
+    pub fn detect(self) -> ColorMode {
+        if self != ColorMode::Auto {
+            return self;
+        }
+
+        match std::env::var("COLORTERM") {
+            Ok(value) if value == "truecolor" || value == "24bit" => ColorMode::Truecolor,
+            _ => ColorMode::Ansi256,
+        }
+    }
+
+    /// The terminal color for `rgb`, or `None` to use the default color.
+    pub fn to_color(self, rgb: Rgb) -> Option<Color> {
+        match self {
+            ColorMode::Auto | ColorMode::Truecolor => Some(Color::Rgb {
+                r: rgb.0,
+                g: rgb.1,
+                b: rgb.2,
+            }),
+            ColorMode::Ansi256 => Some(Color::AnsiValue(to_ansi256(rgb))),
+            ColorMode::None => None,
+        }
+    }
+}
+
+/// The closest color of the 6x6x6 cube in the xterm 256 color palette.
+fn to_ansi256(rgb: Rgb) -> u8 {
+    let level = |value: u8| match value {
+        0..=47 => 0,
+        48..=114 => 1,
+        _ => (value - 35) / 40,
+    };
+    16 + 36 * level(rgb.0) + 6 * level(rgb.1) + level(rgb.2)
+}
+
+/// Colors spread evenly over 0.0..=1.0 and blended in between.
+#[derive(Debug, Clone, Copy)]
+pub struct Gradient(pub &'static [Rgb]);
+
+impl Gradient {
+    /// Green through yellow to red, for levels.
+    pub const LEVEL: Gradient = Gradient(&[Rgb(0, 200, 80), Rgb(240, 220, 0), Rgb(255, 40, 20)]);
+
+    /// The color at `t`, clamped to 0.0..=1.0.
+    pub fn at(&self, t: f32) -> Rgb {
+        let stops = self.0;
+        if stops.len() < 2 {
+            return stops.first().copied().unwrap_or(Rgb(255, 255, 255));
+        }
+
+        let position = t.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+        let index = (position.floor() as usize).min(stops.len() - 2);
+        stops[index].mix(stops[index + 1], position - index as f32)
+    }
+}
+
+/// Perceptually uniform colormaps for intensity plots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Colormap {
+    Viridis,
+    Magma,
+    Inferno,
+    Grayscale,
+}
+
+impl Colormap {
+    /// The colormap sampled at nine evenly spaced points.
+    pub fn gradient(self) -> Gradient {
+        match self {
+            Colormap::Viridis => Gradient(&[
+                Rgb(68, 1, 84),
+                Rgb(71, 45, 123),
+                Rgb(59, 82, 139),
+                Rgb(44, 114, 142),
+                Rgb(33, 145, 140),
+                Rgb(40, 174, 128),
+                Rgb(94, 201, 98),
+                Rgb(173, 220, 48),
+                Rgb(253, 231, 37),
+            ]),
+            Colormap::Magma => Gradient(&[
+                Rgb(0, 0, 4),
+                Rgb(28, 16, 68),
+                Rgb(79, 18, 123),
+                Rgb(129, 37, 129),
+                Rgb(181, 54, 122),
+                Rgb(229, 80, 100),
+                Rgb(251, 135, 97),
+                Rgb(254, 194, 135),
+                Rgb(252, 253, 191),
+            ]),
+            Colormap::Inferno => Gradient(&[
+                Rgb(0, 0, 4),
+                Rgb(31, 12, 72),
+                Rgb(85, 15, 109),
+                Rgb(136, 34, 106),
+                Rgb(186, 54, 85),
+                Rgb(227, 89, 51),
+                Rgb(249, 142, 9),
+                Rgb(249, 203, 53),
+                Rgb(252, 255, 164),
+            ]),
+            Colormap::Grayscale => Gradient(&[Rgb(0, 0, 0), Rgb(255, 255, 255)]),
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use pulse::channelmap::Position;
+
+use crate::{
+    analysis::{meter::ChannelLevel, spectrum::to_db},
+    render::{
+        color::{Gradient, Rgb},
+        screen::{Cell, Rect, Screen},
+    },
+};
+
+/// Lowest level on the meter scale, in dBFS.
+const FLOOR: f32 = -60.0;
+/// Block elements from empty to full in eighths of a cell, left aligned.
+const BLOCKS: [char; 9] = [' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉', '█'];
+/// Color of the unlit part of a meter.
+const DIM: Rgb = Rgb(70, 70, 90);
+/// Color of the maximum true peak marker.
+const HOLD: Rgb = Rgb(230, 230, 230);
+/// Color of the numbers once the true peak went over full scale.
+const OVER: Rgb = Rgb(255, 40, 20);
+
+/// Draws one horizontal meter per channel, from -60 to 0 dBFS, with the
+/// ballistic reading as a bar, the maximum true peak as a marker and the
+/// numbers next to it. The numbers turn red once the true peak went over
+/// full scale.
+pub fn draw(screen: &mut Screen, area: Rect, levels: &[ChannelLevel]) {
+    if levels.is_empty() || area.height == 0 {
+        return;
+    }
+
+    let labels: Vec<String> = levels.iter().map(|level| label(level.position)).collect();
+    let numbers: Vec<String> = levels
+        .iter()
+        .map(|level| {
+            format!(
+                " {:5.1}  peak {:5.1}  true {:5.1}  max {:5.1}  rms {:5.1}",
+                to_db(level.reading, FLOOR),
+                to_db(level.sample_peak, FLOOR),
+                to_db(level.true_peak, FLOOR),
+                to_db(level.max_true_peak, FLOOR),
+                to_db(level.rms, FLOOR),
+            )
+        })
+        .collect();
+    let width = |texts: &[String]| texts.iter().map(|text| text.chars().count()).max();
+    let label_width = width(&labels).unwrap_or(0) as u16 + 1;
+    let numbers_width = width(&numbers).unwrap_or(0) as u16;
+    let bar_width = area.width.saturating_sub(label_width + numbers_width);
+
+    // A blank row between channels, if there is room for it.
+    let spacing = if levels.len() as u16 * 2 <= area.height {
+        2
+    } else {
+        1
+    };
+    for (index, level) in levels.iter().enumerate() {
+        let y = area.y + index as u16 * spacing;
+        if y >= area.y + area.height {
+            break;
+        }
+
+        screen.print(area.x, y, &labels[index], None);
+        bar(screen, area.x + label_width, y, bar_width, level);
+        screen.print(
+            area.x + label_width + bar_width,
+            y,
+            &numbers[index],
+            (level.max_true_peak > 1.0).then_some(OVER),
+        );
+    }
+}
+
+/// A short name for `position`, like `front-left`.
+fn label(position: Position) -> String {
+    Position::to_string(position)
+        .map(|name| name.into_owned())
+        .unwrap_or_else(|| "?".to_string())
+}
+
+fn bar(screen: &mut Screen, x: u16, y: u16, width: u16, level: &ChannelLevel) {
+    if width == 0 {
+        return;
+    }
+
+    let to_position = |amplitude: f32| (to_db(amplitude, FLOOR) - FLOOR) / -FLOOR;
+    let eighths = (to_position(level.reading) * width as f32 * 8.0).round() as usize;
+    let marker = (to_position(level.max_true_peak) * (width - 1) as f32).round() as u16;
+
+    for column in 0..width {
+        let filled = eighths.saturating_sub(column as usize * 8).min(8);
+        let color = Gradient::LEVEL.at((column as f32 + 0.5) / width as f32);
+        let cell = if filled > 0 {
+            Cell {
+                symbol: BLOCKS[filled],
+                color: Some(color),
+                background: None,
+            }
+        } else if column == marker && level.max_true_peak > 0.0 {
+            Cell {
+                symbol: '▏',
+                color: Some(HOLD),
+                background: None,
+            }
+        } else {
+            Cell {
+                symbol: '·',
+                color: Some(DIM),
+                background: None,
+            }
+        };
+        screen.set(x + column, y, cell);
+    }
+}
@@ -0,0 +1,53 @@
+//! Drawing analysis results in the terminal.
+
+use std::time::Duration;
+
+use clap::ValueEnum;
+use color::ColorMode;
+use scope::ScopeLayout;
+use spectrogram::SpectrogramConfig;
+
+pub mod bars;
+pub mod braille;
+pub mod color;
+pub mod meters;
+pub mod scope;
+pub mod screen;
+pub mod spectrogram;
+pub mod terminal;
+pub mod vector;
+
+/// What the terminal shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum View {
+    /// Spectrum bars, one per band.
+    Bars,
+    /// Oscilloscope trace of the left and right channels.
+    Scope,
+    /// Goniometer of the left and right channels with a phase correlation
+    /// meter.
+    Vector,
+    /// Peak, true peak and RMS level meters for every channel.
+    Meters,
+    /// Spectra scrolling by over time, colored by level.
+    Spectrogram,
+}
+
+/// How the terminal views are shown.
+#[derive(Debug, Clone, Copy)]
+pub struct DisplayConfig {
+    /// Time between frames. Frames are drawn at this rate no matter how
+    /// often the server delivers audio.
+    pub frame_interval: Duration,
+    pub color: ColorMode,
+    pub view: View,
+    /// Length of audio the oscilloscope and goniometer show.
+    pub scope_window: Duration,
+    pub scope_layout: ScopeLayout,
+    pub spectrogram: SpectrogramConfig,
+    /// Gain applied to the audio the views show, in dB.
+    pub gain: f32,
+    /// How much of the previous bar heights carries over into the next
+    /// spectrum's, in 0.0..1.0.
+    pub smoothing: f32,
+}
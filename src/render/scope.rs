@@ -0,0 +1,88 @@
+use clap::ValueEnum;
+
+use crate::render::{
+    braille::BrailleCanvas,
+    color::Rgb,
+    screen::{Rect, Screen},
+};
+
+/// Color of the left, or only, channel's trace.
+pub const LEFT: Rgb = Rgb(80, 200, 255);
+/// Color of the right channel's trace.
+pub const RIGHT: Rgb = Rgb(255, 110, 200);
+
+/// How the traces of several channels share the view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ScopeLayout {
+    /// Each channel in its own lane, stacked top to bottom.
+    Separate,
+    /// All channels on top of each other in the whole area.
+    Overlay,
+}
+
+/// Draws each of `traces`, samples in -1.0..=1.0 with their color, as an
+/// oscilloscope trace in braille dots.
+pub fn draw(screen: &mut Screen, area: Rect, traces: &[(&[f32], Rgb)], layout: ScopeLayout) {
+    if traces.is_empty() || area.width == 0 || area.height == 0 {
+        return;
+    }
+
+    match layout {
+        ScopeLayout::Overlay => {
+            let mut canvas = BrailleCanvas::new(area.width, area.height);
+            for &(samples, color) in traces {
+                trace(&mut canvas, samples, color);
+            }
+            canvas.draw(screen, area);
+        }
+        ScopeLayout::Separate => {
+            let lanes = traces.len() as u16;
+            for (index, &(samples, color)) in traces.iter().enumerate() {
+                let index = index as u16;
+                let top = area.height * index / lanes;
+                let lane = Rect {
+                    x: area.x,
+                    y: area.y + top,
+                    width: area.width,
+                    height: area.height * (index + 1) / lanes - top,
+                };
+
+                let mut canvas = BrailleCanvas::new(lane.width, lane.height);
+                trace(&mut canvas, samples, color);
+                canvas.draw(screen, lane);
+            }
+        }
+    }
+}
+
+/// Plots `samples` across the whole canvas. When there are more samples
+/// than dot columns, each column spans the range of the samples it covers,
+/// so short peaks aren't lost.
+fn trace(canvas: &mut BrailleCanvas, samples: &[f32], color: Rgb) {
+    let (width, height) = (canvas.dot_width(), canvas.dot_height());
+    if samples.is_empty() || height == 0 {
+        return;
+    }
+
+    let to_y = |sample: f32| {
+        ((1.0 - sample.clamp(-1.0, 1.0)) / 2.0 * (height - 1) as f32).round() as usize
+    };
+
+    let mut previous = None;
+    for x in 0..width {
+        let start = (x * samples.len() / width).min(samples.len() - 1);
+        let end = ((x + 1) * samples.len() / width).clamp(start + 1, samples.len());
+        let column = &samples[start..end];
+
+        let (low, high) = column
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(low, high), &sample| {
+                (low.min(sample), high.max(sample))
+            });
+        if let Some(previous) = previous {
+            canvas.line(previous, (x, to_y(column[0])), color);
+        }
+        canvas.line((x, to_y(high)), (x, to_y(low)), color);
+        previous = Some((x, to_y(column[column.len() - 1])));
+    }
+}
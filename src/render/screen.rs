@@ -0,0 +1,94 @@
+use crate::render::color::Rgb;
+
+/// One character cell of the terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub symbol: char,
+    pub color: Option<Rgb>,
+    pub background: Option<Rgb>,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Cell {
+            symbol: ' ',
+            color: None,
+            background: None,
+        }
+    }
+}
+
+/// A rectangle of cells, in cells from the top left corner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+/// An offscreen frame that views draw into and the terminal then shows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Screen {
+    width: u16,
+    height: u16,
+    cells: Vec<Cell>,
+}
+
+impl Screen {
+    pub fn new(width: u16, height: u16) -> Self {
+        Screen {
+            width,
+            height,
+            cells: vec![Cell::default(); width as usize * height as usize],
+        }
+    }
+
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
+    /// The whole screen.
+    pub fn area(&self) -> Rect {
+        Rect {
+            x: 0,
+            y: 0,
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    /// Sets the cell at `x`, `y`. Cells outside the screen are ignored.
+    pub fn set(&mut self, x: u16, y: u16, cell: Cell) {
+        if x < self.width && y < self.height {
+            self.cells[y as usize * self.width as usize + x as usize] = cell;
+        }
+    }
+
+    /// Writes `text` starting at `x`, `y`, cut off at the right edge.
+    pub fn print(&mut self, x: u16, y: u16, text: &str, color: Option<Rgb>) {
+        for (offset, symbol) in text.chars().enumerate() {
+            let Some(x) = x.checked_add(offset as u16) else {
+                break;
+            };
+            self.set(
+                x,
+                y,
+                Cell {
+                    symbol,
+                    color,
+                    background: None,
+                },
+            );
+        }
+    }
+
+    pub fn row(&self, y: u16) -> &[Cell] {
+        let start = y as usize * self.width as usize;
+        &self.cells[start..start + self.width as usize]
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::VecDeque;
+
+use crate::{
+    analysis::{
+        bands::{BandConfig, BandMapper, BandScale},
+        spectrum::{to_db, Spectrum},
+    },
+    render::{
+        color::{Colormap, Gradient, Rgb},
+        screen::{Cell, Rect, Screen},
+    },
+};
+
+/// Frequency rows kept per spectrum. They are resampled to the height of
+/// the output when drawn.
+const ROWS: usize = 256;
+/// Most spectra kept, enough for the widest terminals and for images.
+const MAX_COLUMNS: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpectrogramConfig {
+    pub colormap: Colormap,
+    /// Level shown as the bottom of the colormap, in dBFS.
+    pub db_min: f32,
+    /// Level shown as the top of the colormap, in dBFS.
+    pub db_max: f32,
+    /// Frequency axis, spanning the frequency range of the bands.
+    pub scale: BandScale,
+}
+
+/// An image with 4 bytes per pixel, red, green, blue and alpha, row by row
+/// from the top left corner.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbaImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl RgbaImage {
+    fn pixel(&self, x: usize, y: usize) -> Rgb {
+        let offset = (y * self.width + x) * 4;
+        Rgb(
+            self.pixels[offset],
+            self.pixels[offset + 1],
+            self.pixels[offset + 2],
+        )
+    }
+}
+
+/// A scrolling history of spectra, time running left to right and
+/// frequency bottom to top, colored by level.
+pub struct Spectrogram {
+    config: SpectrogramConfig,
+    gradient: Gradient,
+    mapper: BandMapper,
+    /// Levels in dBFS, one column per spectrum, lowest frequency first.
+    /// They're clamped to the level range only when drawn, so the range
+    /// can change without redoing the history.
+    columns: VecDeque<Vec<f32>>,
+}
+
+impl Spectrogram {
+    /// A spectrogram over the frequency range of `bands`.
+    pub fn new(config: SpectrogramConfig, bands: &BandConfig) -> Self {
+        Spectrogram {
+            config,
+            gradient: config.colormap.gradient(),
+            mapper: BandMapper::new(BandConfig {
+                scale: config.scale,
+                count: ROWS,
+                ..*bands
+            }),
+            columns: VecDeque::with_capacity(MAX_COLUMNS),
+        }
+    }
+
+    /// Switches to the colormap and level range of `config`, keeping the
+    /// spectra seen so far. Its scale has to be the current one.
+    pub fn set_colors(&mut self, config: SpectrogramConfig) {
+        debug_assert_eq!(config.scale, self.config.scale);
+        self.config = config;
+        self.gradient = config.colormap.gradient();
+    }
+
+    /// Adds `spectrum` as the newest column.
+    pub fn push(&mut self, spectrum: &Spectrum) {
+        if self.columns.len() == MAX_COLUMNS {
+            self.columns.pop_front();
+        }
+
+        let column = self
+            .mapper
+            .map(spectrum)
+            .into_iter()
+            .map(|magnitude| to_db(magnitude, f32::NEG_INFINITY))
+            .collect();
+        self.columns.push_back(column);
+    }
+
+    /// Renders the latest `width` spectra into an image of `width` by
+    /// `height` pixels. Columns from before the first spectrum show the
+    /// bottom of the colormap.
+    pub fn to_rgba(&self, width: usize, height: usize) -> RgbaImage {
+        let background = self.gradient.at(0.0);
+        let range = (self.config.db_max - self.config.db_min).max(f32::EPSILON);
+        let mut pixels = Vec::with_capacity(width * height * 4);
+
+        for y in 0..height {
+            for x in 0..width {
+                let column = (self.columns.len() + x)
+                    .checked_sub(width)
+                    .and_then(|index| self.columns.get(index));
+                let color = match column {
+                    Some(rows) if !rows.is_empty() => {
+                        // Row 0 is the lowest frequency, pixel row 0 the top.
+                        let from_bottom = height - 1 - y;
+                        let start = from_bottom * rows.len() / height;
+                        let end = ((from_bottom + 1) * rows.len() / height).max(start + 1);
+                        let level = rows[start..end.min(rows.len())]
+                            .iter()
+                            .copied()
+                            .fold(f32::MIN, f32::max);
+                        self.gradient.at((level - self.config.db_min) / range)
+                    }
+                    _ => background,
+                };
+                pixels.extend_from_slice(&[color.0, color.1, color.2, 255]);
+            }
+        }
+
+        RgbaImage {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Draws the spectrogram into `area` with two pixels per cell, using
+    /// the upper half block with the foreground as the top pixel and the
+    /// background as the bottom one.
+    pub fn draw(&self, screen: &mut Screen, area: Rect) {
+        let image = self.to_rgba(area.width as usize, area.height as usize * 2);
+        for row in 0..area.height {
+            for column in 0..area.width {
+                let (x, y) = (column as usize, row as usize * 2);
+                screen.set(
+                    area.x + column,
+                    area.y + row,
+                    Cell {
+                        symbol: '▀',
+                        color: Some(image.pixel(x, y)),
+                        background: Some(image.pixel(x, y + 1)),
+                    },
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::{
+    io::{self, Stdout, Write},
+    time::Duration,
+};
+
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::{Print, ResetColor, SetBackgroundColor, SetForegroundColor},
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+
+use crate::{
+    logging,
+    render::{color::ColorMode, screen::Screen},
+};
+
+/// The terminal in full screen mode, restored when dropped.
+///
+/// Only rows that changed since the last frame are sent, which keeps the
+/// output small enough to run smoothly over SSH. Log output to stderr is
+/// held back meanwhile.
+pub struct Terminal {
+    out: Stdout,
+    color: ColorMode,
+    previous: Option<Screen>,
+}
+
+impl Terminal {
+    /// Switches to the alternate screen in raw mode and hides the cursor.
+    pub fn enter(color: ColorMode) -> io::Result<Terminal> {
+        let mut out = io::stdout();
+        terminal::enable_raw_mode()?;
+        if let Err(err) = execute!(out, EnterAlternateScreen, Hide, Clear(ClearType::All)) {
+            let _ = terminal::disable_raw_mode();
+            return Err(err);
+        }
+
+        let color = color.detect();
+        log::debug!(target: logging::RENDER, "Entered the terminal, sending {:?} colors.", color);
+        logging::hold_stderr();
+
+        Ok(Terminal {
+            out,
+            color,
+            previous: None,
+        })
+    }
+
+    /// Sends `color` from the next frame on, which is then drawn in full.
+    pub fn set_color(&mut self, color: ColorMode) {
+        self.color = color.detect();
+        self.previous = None;
+        log::debug!(target: logging::RENDER, "Sending {:?} colors.", self.color);
+    }
+
+    /// The size of the terminal in cells.
+    pub fn size(&self) -> io::Result<(u16, u16)> {
+        terminal::size()
+    }
+
+    /// Shows `screen`. A screen of a different size than the last one
+    /// redraws everything.
+    pub fn draw(&mut self, screen: &Screen) -> io::Result<()> {
+        let resized = self.previous.as_ref().is_none_or(|previous| {
+            (previous.width(), previous.height()) != (screen.width(), screen.height())
+        });
+        if resized {
+            queue!(self.out, ResetColor, Clear(ClearType::All))?;
+        }
+
+        for y in 0..screen.height() {
+            let row = screen.row(y);
+            if !resized && self.previous.as_ref().map(|previous| previous.row(y)) == Some(row) {
+                continue;
+            }
+
+            queue!(self.out, MoveTo(0, y), ResetColor)?;
+            let mut colors = (None, None);
+            let mut run = String::new();
+            for cell in row {
+                let cell_colors = (
+                    cell.color.and_then(|rgb| self.color.to_color(rgb)),
+                    cell.background.and_then(|rgb| self.color.to_color(rgb)),
+                );
+                if cell_colors != colors {
+                    queue!(self.out, Print(&run), ResetColor)?;
+                    run.clear();
+                    if let Some(color) = cell_colors.0 {
+                        queue!(self.out, SetForegroundColor(color))?;
+                    }
+                    if let Some(background) = cell_colors.1 {
+                        queue!(self.out, SetBackgroundColor(background))?;
+                    }
+                    colors = cell_colors;
+                }
+                run.push(cell.symbol);
+            }
+            queue!(self.out, Print(&run))?;
+        }
+
+        self.out.flush()?;
+        self.previous = Some(screen.clone());
+        Ok(())
+    }
+
+    /// Handles pending input without blocking. Returns true if the user
+    /// asked to quit with `q`, Escape or Ctrl-C, which raw mode delivers as
+    /// a key instead of a signal.
+    pub fn quit_requested(&mut self) -> io::Result<bool> {
+        while event::poll(Duration::ZERO)? {
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(true),
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        return Ok(true)
+                    }
+                    _ => {}
+                },
+                Event::Resize(..) => self.previous = None,
+                _ => {}
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = execute!(self.out, ResetColor, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+        logging::release_stderr();
+        log::debug!(target: logging::RENDER, "Restored the terminal.");
+    }
+}
@@ -0,0 +1,103 @@
+use crate::{
+    analysis::stereo,
+    render::{
+        braille::BrailleCanvas,
+        color::{Gradient, Rgb},
+        screen::{Cell, Rect, Screen},
+    },
+};
+
+/// Color of the plotted frames.
+const TRACE: Rgb = Rgb(120, 255, 160);
+/// Color of the mono and left/right guide lines.
+const GUIDE: Rgb = Rgb(70, 70, 90);
+/// Correlation meter colors from -1.0 to +1.0.
+const CORRELATION: Gradient = Gradient(&[Rgb(255, 40, 20), Rgb(240, 220, 0), Rgb(0, 200, 80)]);
+
+/// Draws a goniometer of the `left` and `right` samples above a phase
+/// correlation meter showing `correlation`.
+pub fn draw(screen: &mut Screen, area: Rect, left: &[f32], right: &[f32], correlation: f32) {
+    if area.width == 0 || area.height < 2 {
+        return;
+    }
+
+    let plot = Rect {
+        height: area.height - 1,
+        ..area
+    };
+    goniometer(screen, plot, left, right);
+    correlation_meter(
+        screen,
+        area.x,
+        area.y + plot.height,
+        area.width,
+        correlation,
+    );
+}
+
+/// Plots each frame as a dot, side horizontally and mid vertically, in the
+/// largest square that fits, with guides for mono and left/right only.
+fn goniometer(screen: &mut Screen, area: Rect, left: &[f32], right: &[f32]) {
+    let mut canvas = BrailleCanvas::new(area.width, area.height);
+    // Braille dots are about square, so a square in dots looks square.
+    let size = canvas.dot_width().min(canvas.dot_height());
+    if size < 2 {
+        return;
+    }
+    let origin_x = (canvas.dot_width() - size) / 2;
+    let origin_y = (canvas.dot_height() - size) / 2;
+    let last = (size - 1) as f32;
+    let to_dot = |x: f32, y: f32| {
+        (
+            origin_x + ((x.clamp(-1.0, 1.0) + 1.0) / 2.0 * last).round() as usize,
+            origin_y + ((1.0 - y.clamp(-1.0, 1.0)) / 2.0 * last).round() as usize,
+        )
+    };
+
+    canvas.line(to_dot(0.0, -1.0), to_dot(0.0, 1.0), GUIDE);
+    canvas.line(to_dot(-1.0, 1.0), to_dot(1.0, -1.0), GUIDE);
+    canvas.line(to_dot(1.0, 1.0), to_dot(-1.0, -1.0), GUIDE);
+
+    for (&left, &right) in left.iter().zip(right) {
+        let (mid, side) = stereo::mid_side(left, right);
+        let (x, y) = to_dot(-side, mid);
+        canvas.set(x, y, TRACE);
+    }
+
+    canvas.draw(screen, area);
+}
+
+/// A one line meter from -1 to +1 with a marker at `correlation`.
+fn correlation_meter(screen: &mut Screen, x: u16, y: u16, width: u16, correlation: f32) {
+    let label = format!(" {:+.2}", correlation);
+    let scale_width = width.saturating_sub(6 + label.len() as u16);
+    if scale_width < 3 {
+        screen.print(x, y, label.trim_start(), None);
+        return;
+    }
+
+    screen.print(x, y, "-1 ", None);
+    let scale_x = x + 3;
+    let marker = ((correlation.clamp(-1.0, 1.0) + 1.0) / 2.0 * (scale_width - 1) as f32).round();
+    let color = CORRELATION.at((correlation + 1.0) / 2.0);
+    for column in 0..scale_width {
+        let (symbol, color) = if column == marker as u16 {
+            ('█', color)
+        } else if column == scale_width / 2 {
+            ('┼', GUIDE)
+        } else {
+            ('─', GUIDE)
+        };
+        screen.set(
+            scale_x + column,
+            y,
+            Cell {
+                symbol,
+                color: Some(color),
+                background: None,
+            },
+        );
+    }
+    screen.print(scale_x + scale_width, y, " +1", None);
+    screen.print(scale_x + scale_width + 3, y, &label, Some(color));
+}
@@ -0,0 +1,102 @@
+//! The sound server operations capture relies on, as traits, so capture can
+//! run against something other than a live PulseAudio server.
+
+use std::time::Duration;
+
+use pulse::{channelmap::Map, error::PAErr, sample::Spec};
+
+use crate::{
+    connection::Connection,
+    device::{ServerDefaults, Sink, Source},
+    error::Error,
+};
+
+/// What reading a record stream turned up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Peek {
+    /// Nothing buffered right now.
+    Empty,
+    /// The server dropped this many bytes.
+    Hole(usize),
+    /// Raw bytes in the stream's sample spec.
+    Data(Vec<u8>),
+}
+
+/// Where a record stream is in its lifecycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamState {
+    /// Created, but not delivering audio yet.
+    Connecting,
+    Ready,
+    /// The stream failed or was terminated by the server.
+    Failed(PAErr),
+}
+
+/// A record stream on a source.
+pub trait RecordStream {
+    fn state(&self) -> StreamState;
+
+    /// Takes the next fragment out of the stream's buffer.
+    fn read(&mut self) -> Result<Peek, Error>;
+
+    /// The server's index of the stream, once it has one.
+    fn index(&self) -> Option<u32>;
+
+    fn disconnect(&mut self) -> Result<(), Error>;
+}
+
+/// A connection to a sound server.
+///
+/// Everything happens on the thread that connected. Requests block until
+/// the server answered; [`Server::iterate`] lets streams and subscriptions
+/// make progress in between.
+pub trait Server {
+    type Stream: RecordStream;
+
+    fn sinks(&self) -> Result<Vec<Sink>, Error>;
+
+    fn sources(&self) -> Result<Vec<Source>, Error>;
+
+    fn server_defaults(&self) -> Result<ServerDefaults, Error>;
+
+    /// Moves the source output (record stream) with `index` to the source
+    /// named `source_name`.
+    fn move_source_output(&self, index: u32, source_name: &str) -> Result<(), Error>;
+
+    /// Creates a record stream on the source named `source`.
+    fn record(&self, source: &str, spec: &Spec, map: &Map) -> Result<Self::Stream, Error>;
+
+    /// Starts watching for changes that can change the default sink, which
+    /// [`Server::default_sink_changed`] then reports.
+    fn watch_default_sink(&self);
+
+    /// Whether the default sink may have changed since the last call.
+    fn default_sink_changed(&self) -> bool;
+
+    /// Waits until something happens or `timeout` passes. Fails once the
+    /// server went away.
+    fn iterate(&self, timeout: Duration) -> Result<(), Error>;
+
+    fn disconnect(&self);
+}
+
+/// Opens [`Server`] connections, once at the start and again after every
+/// lost connection. It's handed to the capture thread, so it has to be
+/// `Send`; the servers it opens stay on that thread.
+pub trait Connector: Send + 'static {
+    type Server: Server;
+
+    fn connect(&self) -> Result<Self::Server, Error>;
+}
+
+/// Connects to the default PulseAudio server.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PulseConnector;
+
+impl Connector for PulseConnector {
+    type Server = Connection;
+
+    fn connect(&self) -> Result<Connection, Error> {
+        Connection::connect()
+    }
+}
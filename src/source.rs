@@ -0,0 +1,164 @@
+use std::{
+    path::PathBuf,
+    sync::mpsc::RecvTimeoutError,
+    thread,
+    time::{Duration, Instant},
+};
+
+use pulse::sample::Spec;
+
+use crate::{
+    capture::{Capture, CaptureConfig, CaptureEvent},
+    error::Error,
+    generator::{GeneratorConfig, GeneratorSource},
+    logging,
+    wav::WavReader,
+};
+
+/// How many chunks each second of a file is delivered in.
+const FILE_CHUNKS_PER_SECOND: u32 = 100;
+
+/// Where the audio to analyze comes from.
+///
+/// Sources report through the same [`CaptureEvent`]s as the live record
+/// stream: `Ready` once the format is known, then `Data` and `Hole`.
+/// Stopping a source is dropping it.
+pub trait AudioSource {
+    /// Waits up to `timeout` for the next event. Returns
+    /// `RecvTimeoutError::Disconnected` once the source has ended.
+    fn recv_timeout(&mut self, timeout: Duration) -> Result<CaptureEvent, RecvTimeoutError>;
+
+    /// Switches to `config` without starting over, announced by a new
+    /// `Ready`. Returns false if the source can't, and has to be replaced
+    /// by a newly opened one instead.
+    fn reconfigure(&mut self, config: &SourceConfig) -> bool {
+        let _ = config;
+        false
+    }
+}
+
+/// Which [`AudioSource`] to open.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SourceConfig {
+    /// The monitor of a sink on the sound server.
+    Capture(Box<CaptureConfig>),
+    /// A WAV file, delivered in real time if `paced` and as fast as it can
+    /// be read otherwise.
+    File { path: PathBuf, paced: bool },
+    /// A synthetic test signal, in real time.
+    Generator(Box<GeneratorConfig>),
+}
+
+impl SourceConfig {
+    pub fn open(self) -> Result<Box<dyn AudioSource>, Error> {
+        match self {
+            SourceConfig::Capture(config) => Ok(Box::new(Capture::start(*config))),
+            SourceConfig::File { path, paced } => {
+                Ok(Box::new(FileSource::new(WavReader::open(&path)?, paced)))
+            }
+            SourceConfig::Generator(config) => Ok(Box::new(GeneratorSource::new(*config)?)),
+        }
+    }
+}
+
+/// Holds back audio until the audio before it has had time to play, so a
+/// source that can produce audio instantly delivers it in real time.
+pub struct Pacer {
+    spec: Spec,
+    started: Instant,
+    /// Bytes of audio delivered so far.
+    delivered: u64,
+}
+
+impl Pacer {
+    /// Starts the clock for audio in `spec`.
+    pub fn start(spec: Spec) -> Self {
+        Pacer {
+            spec,
+            started: Instant::now(),
+            delivered: 0,
+        }
+    }
+
+    /// Waits until the next audio is due, but at most `timeout`. Returns
+    /// false if it isn't due yet.
+    pub fn wait(&self, timeout: Duration) -> bool {
+        let played = Duration::from_micros(self.spec.bytes_to_usec(self.delivered).0);
+        let wait = (self.started + played).saturating_duration_since(Instant::now());
+        if wait > timeout {
+            thread::sleep(timeout);
+            return false;
+        }
+
+        thread::sleep(wait);
+        true
+    }
+
+    /// Counts `len` bytes as delivered.
+    pub fn delivered(&mut self, len: usize) {
+        self.delivered += len as u64;
+    }
+}
+
+/// Plays a WAV file as if it were being recorded.
+pub struct FileSource {
+    reader: WavReader,
+    paced: bool,
+    ready: bool,
+    /// Set when `Ready` is sent if the file is played in real time.
+    pacer: Option<Pacer>,
+    buf: Vec<u8>,
+}
+
+impl FileSource {
+    pub fn new(reader: WavReader, paced: bool) -> Self {
+        let spec = reader.spec();
+        let frames = (spec.rate / FILE_CHUNKS_PER_SECOND).max(1) as usize;
+        FileSource {
+            buf: vec![0; frames * spec.frame_size()],
+            reader,
+            paced,
+            ready: false,
+            pacer: None,
+        }
+    }
+}
+
+impl AudioSource for FileSource {
+    fn recv_timeout(&mut self, timeout: Duration) -> Result<CaptureEvent, RecvTimeoutError> {
+        let spec = self.reader.spec();
+        if !self.ready {
+            self.ready = true;
+            self.pacer = self.paced.then(|| Pacer::start(spec));
+            log::info!(
+                target: logging::STREAM,
+                "Reading {} in {}.",
+                self.reader.path().display(),
+                spec.print()
+            );
+            return Ok(CaptureEvent::Ready {
+                sink: self.reader.path().display().to_string(),
+                source: "file".to_string(),
+                spec,
+                channel_map: self.reader.channel_map(),
+            });
+        }
+
+        if let Some(pacer) = &self.pacer {
+            if !pacer.wait(timeout) {
+                return Err(RecvTimeoutError::Timeout);
+            }
+        }
+
+        match self.reader.read(&mut self.buf) {
+            Ok(0) => Err(RecvTimeoutError::Disconnected),
+            Ok(len) => {
+                if let Some(pacer) = &mut self.pacer {
+                    pacer.delivered(len);
+                }
+                Ok(CaptureEvent::Data(self.buf[..len].to_vec()))
+            }
+            Err(err) => Ok(CaptureEvent::Failed(err)),
+        }
+    }
+}
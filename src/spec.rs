@@ -0,0 +1,88 @@
+use pulse::{
+    channelmap::{Map, MapDef},
+    sample::{Format, Spec},
+};
+
+use crate::{decode, error::Error};
+
+/// The sample spec and channel map asked for on the command line. Anything
+/// left unset is taken from the device being recorded.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SpecRequest {
+    pub format: Option<Format>,
+    pub rate: Option<u32>,
+    pub channels: Option<u8>,
+    pub channel_map: Option<Map>,
+}
+
+impl SpecRequest {
+    /// Fills in the unset fields from the device's native spec and map.
+    ///
+    /// A native format the decoder can't handle is replaced with F32LE and
+    /// left to the server to convert. Without an explicit channel map, the
+    /// native one is kept if the channel count matches, otherwise the
+    /// server's default layout for that many channels is used.
+    pub fn resolve(&self, native_spec: &Spec, native_map: &Map) -> Result<(Spec, Map), Error> {
+        let native_format = if decode::is_supported(native_spec.format) {
+            native_spec.format
+        } else {
+            Format::F32le
+        };
+
+        let channels = self
+            .channels
+            .or(self.channel_map.map(|map| map.get().len() as u8))
+            .unwrap_or(native_spec.channels);
+
+        let spec = Spec {
+            format: self.format.unwrap_or(native_format),
+            channels,
+            rate: self.rate.unwrap_or(native_spec.rate),
+        };
+
+        if !spec.is_valid() {
+            return Err(Error::InvalidSpec(format!(
+                "{} channels at {} Hz is not a valid sample spec",
+                spec.channels, spec.rate
+            )));
+        }
+
+        let map = match self.channel_map {
+            Some(map) => map,
+            None if channels == native_spec.channels => *native_map,
+            None => *Map::default().init_extend(channels, MapDef::default()),
+        };
+
+        if !map.is_compatible_with_sample_spec(&spec) {
+            return Err(Error::InvalidSpec(format!(
+                "channel map {} does not have {} channels",
+                map.print(),
+                channels
+            )));
+        }
+
+        Ok((spec, map))
+    }
+}
+
+/// Parses a sample format name like `s16le` or `float32le`.
+pub fn parse_format(s: &str) -> Result<Format, String> {
+    match Format::parse(s) {
+        Format::Invalid => Err(format!("unknown sample format \"{}\"", s)),
+        format if !decode::is_supported(format) => Err(format!(
+            "sample format \"{}\" is not supported, use u8, s16le, s24le, s32le or float32le",
+            s
+        )),
+        format => Ok(format),
+    }
+}
+
+/// Parses a channel map, either a comma separated list of positions like
+/// `front-left,front-right,lfe` or a well-known name like `stereo` or
+/// `surround-51`.
+pub fn parse_channel_map(s: &str) -> Result<Map, String> {
+    match Map::new_from_string(s) {
+        Ok(map) if map.is_valid() => Ok(map),
+        _ => Err(format!("invalid channel map \"{}\"", s)),
+    }
+}
@@ -0,0 +1,139 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use signal_hook::consts::{SIGINT, SIGTERM};
+
+use crate::decode::AudioBlock;
+
+/// When to stop, besides on SIGINT or SIGTERM and when the source ends.
+#[derive(Debug, Clone, Copy)]
+pub struct StopConfig {
+    /// How long to run for. Forever if unset.
+    pub duration: Option<Duration>,
+    /// Stop once the audio stayed below `silence_threshold` for this long.
+    pub silence: Option<Duration>,
+    /// Peak level in dBFS up to which audio counts as silence.
+    pub silence_threshold: f32,
+    /// Stop after drawing this many frames.
+    pub frames: Option<u64>,
+}
+
+/// Why running stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    Interrupted,
+    Duration,
+    Silence,
+    Frames,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::Interrupted => write!(f, "Interrupted."),
+            StopReason::Duration => write!(f, "Run duration reached."),
+            StopReason::Silence => write!(f, "Stopped after silence."),
+            StopReason::Frames => write!(f, "Frame limit reached."),
+        }
+    }
+}
+
+/// Keeps track of the [`StopConfig`] conditions and of SIGINT and SIGTERM.
+///
+/// Signals only set a flag, so whoever runs the loop gets to stop the
+/// source and finish its outputs. Silence is measured in audio time, so it
+/// works the same for files read faster than real time.
+pub struct StopConditions {
+    config: StopConfig,
+    started: Instant,
+    interrupted: Arc<AtomicBool>,
+    /// Peak amplitude above which audio isn't silence.
+    threshold: f32,
+    /// Seconds of audio since the last sample above the threshold.
+    silent_for: f64,
+    frames: u64,
+}
+
+impl StopConditions {
+    /// Starts the clock and catches SIGINT and SIGTERM from now on.
+    pub fn start(config: StopConfig) -> Self {
+        let interrupted = Arc::new(AtomicBool::new(false));
+        for signal in [SIGINT, SIGTERM] {
+            signal_hook::flag::register(signal, interrupted.clone())
+                .expect("failed to register signal handler");
+        }
+
+        StopConditions {
+            config,
+            started: Instant::now(),
+            interrupted,
+            threshold: 10f32.powf(config.silence_threshold / 20.0),
+            silent_for: 0.0,
+            frames: 0,
+        }
+    }
+
+    /// Looks for sound in `block`.
+    pub fn push(&mut self, block: &AudioBlock) {
+        if self.config.silence.is_none() || block.rate == 0 {
+            return;
+        }
+
+        // Only the silence after the last loud frame counts.
+        let last_loud = (0..block.len()).rev().find(|&index| {
+            block
+                .channels
+                .iter()
+                .any(|channel| channel[index].abs() > self.threshold)
+        });
+        let silent_frames = match last_loud {
+            Some(index) => {
+                self.silent_for = 0.0;
+                block.len() - index - 1
+            }
+            None => block.len(),
+        };
+        self.silent_for += silent_frames as f64 / block.rate as f64;
+    }
+
+    /// Counts a drawn frame.
+    pub fn frame_drawn(&mut self) {
+        self.frames += 1;
+    }
+
+    /// Time left until the run duration is reached, if there is one.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.config
+            .duration
+            .map(|duration| duration.saturating_sub(self.started.elapsed()))
+    }
+
+    /// Whether to stop now, and why.
+    pub fn reason(&self) -> Option<StopReason> {
+        if self.interrupted.load(Ordering::Relaxed) {
+            Some(StopReason::Interrupted)
+        } else if self.remaining() == Some(Duration::ZERO) {
+            Some(StopReason::Duration)
+        } else if self
+            .config
+            .silence
+            .is_some_and(|silence| self.silent_for >= silence.as_secs_f64())
+        {
+            Some(StopReason::Silence)
+        } else if self
+            .config
+            .frames
+            .is_some_and(|frames| self.frames >= frames)
+        {
+            Some(StopReason::Frames)
+        } else {
+            None
+        }
+    }
+}
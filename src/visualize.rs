@@ -0,0 +1,490 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+    sync::mpsc::RecvTimeoutError,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    analysis::{
+        self,
+        bands::{BandConfig, BandMapper},
+        loudness::{LoudnessMeter, LoudnessReading, LoudnessSummary},
+        meter::LevelMeter,
+        spectrum::{self, SpectrumAnalyzer, SpectrumConfig},
+        stereo::CorrelationMeter,
+        waveform::WaveformBuffer,
+        AnalysisConfig,
+    },
+    capture::CaptureEvent,
+    decode::{AudioBlock, Decoder},
+    error::Error,
+    logging,
+    reload::{Reload, Settings},
+    render::{
+        bars,
+        color::Gradient,
+        meters, scope,
+        screen::{Rect, Screen},
+        spectrogram::Spectrogram,
+        terminal::Terminal,
+        vector, DisplayConfig, View,
+    },
+    source::AudioSource,
+    stop::{StopConditions, StopConfig},
+};
+
+/// Averaging time of the phase correlation meter, in the range hardware
+/// meters use.
+const CORRELATION_INTEGRATION: Duration = Duration::from_millis(300);
+
+/// Shows the selected view of the audio from the settings' source in the
+/// terminal until a stop condition is met, `q` is pressed or the source
+/// ends, then prints a loudness summary. Readings are appended to
+/// `loudness_log` as JSON lines if given. With `reload`, changed settings
+/// are applied as they come.
+pub fn visualize(
+    mut settings: Settings,
+    loudness_log: Option<PathBuf>,
+    stop: StopConfig,
+    mut reload: Option<Reload>,
+) -> Result<(), Error> {
+    let mut view = ViewState::new(&settings.display, &settings.analysis);
+    let mut loudness = Loudness::new(settings.analysis.loudness_target, loudness_log)?;
+    let mut stop = StopConditions::start(stop);
+    let mut source = settings.source.clone().open()?;
+    let mut terminal = Terminal::enter(settings.display.color).map_err(Error::Terminal)?;
+
+    let result = visualize_events(
+        &mut source,
+        &mut terminal,
+        &mut view,
+        &mut loudness,
+        &mut stop,
+        &mut settings,
+        reload.as_mut(),
+    );
+
+    drop(terminal);
+    drop(source);
+
+    // The log is flushed even if visualizing failed, but only a complete
+    // run gets a summary.
+    let summary = loudness.finish()?;
+    result?;
+    println!("{}", summary);
+
+    Ok(())
+}
+
+/// Analyzes audio from `source` and draws a frame at the configured rate
+/// until a stop condition is met, the user quits or the source ends.
+/// Reloaded settings are checked for once per frame.
+fn visualize_events(
+    source: &mut Box<dyn AudioSource>,
+    terminal: &mut Terminal,
+    view: &mut ViewState,
+    loudness: &mut Loudness,
+    stop: &mut StopConditions,
+    settings: &mut Settings,
+    mut reload: Option<&mut Reload>,
+) -> Result<(), Error> {
+    let mut decoder = None;
+    let mut status = String::from("Connecting.");
+    let mut next_frame = Instant::now();
+
+    while stop.reason().is_none() {
+        let until_frame = next_frame.saturating_duration_since(Instant::now());
+        let timeout = stop
+            .remaining()
+            .map_or(until_frame, |remaining| remaining.min(until_frame));
+        let block = match source.recv_timeout(timeout) {
+            Ok(CaptureEvent::Ready {
+                sink,
+                source,
+                spec,
+                channel_map,
+            }) => {
+                status = format!(
+                    "{} via {}, {} ({})",
+                    sink,
+                    source,
+                    spec.print(),
+                    channel_map.print()
+                );
+                log::debug!(
+                    target: logging::ANALYSIS,
+                    "Decoding {} ({}).",
+                    spec.print(),
+                    channel_map.print()
+                );
+                decoder = Some(Decoder::new(&spec, &channel_map)?);
+                None
+            }
+            Ok(CaptureEvent::Switched { sink, source }) => {
+                status = format!("Default sink changed, now {} via {}", sink, source);
+                None
+            }
+            Ok(CaptureEvent::Rejected(err)) => {
+                status = format!("{} Keeping the previous source.", err);
+                None
+            }
+            Ok(CaptureEvent::Reconnecting {
+                error,
+                attempt,
+                delay,
+            }) => {
+                status = format!(
+                    "{} Reconnecting in {:?} (attempt {}).",
+                    error, delay, attempt
+                );
+                None
+            }
+            Ok(CaptureEvent::Hole(len)) => decoder.as_mut().map(|decoder| decoder.decode_hole(len)),
+            Ok(CaptureEvent::Data(data)) => decoder.as_mut().map(|decoder| decoder.decode(&data)),
+            Ok(CaptureEvent::Failed(err)) => return Err(err),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+
+        if let Some(mut block) = block {
+            loudness.push(&block)?;
+            stop.push(&block);
+            if settings.display.gain != 0.0 {
+                block.amplify(10f32.powf(settings.display.gain / 20.0));
+            }
+            view.push(&block);
+        }
+
+        let now = Instant::now();
+        if now < next_frame {
+            continue;
+        }
+        // A late frame is drawn once, and the ticks it missed are skipped
+        // rather than drawn in a burst.
+        let late = now - next_frame;
+        let missed = late.as_nanos() / settings.display.frame_interval.as_nanos().max(1);
+        if missed > 0 {
+            log::debug!(
+                target: logging::RENDER,
+                "Frame late by {:?}, skipping {} missed frames.",
+                late,
+                missed
+            );
+        }
+        next_frame = (next_frame + settings.display.frame_interval).max(now);
+
+        if terminal.quit_requested().map_err(Error::Terminal)? {
+            break;
+        }
+        if let Some(reload) = reload.as_deref_mut() {
+            match reload.poll() {
+                Some(Ok(new)) => {
+                    status = match apply(new, settings, source, &mut decoder, terminal, view) {
+                        Ok(()) => format!("Reloaded {}.", reload.path().display()),
+                        Err(err) => format!("{} Keeping the previous source.", err),
+                    };
+                    log::info!(target: logging::CONFIG, "{}", status);
+                }
+                Some(Err(err)) => {
+                    status = format!("{} Keeping the previous settings.", err);
+                    log::warn!(target: logging::CONFIG, "{}", status);
+                }
+                None => {}
+            }
+        }
+        draw(
+            terminal,
+            view,
+            &settings.display,
+            &status,
+            &loudness.meter.reading(),
+        )?;
+        stop.frame_drawn();
+    }
+
+    Ok(())
+}
+
+/// Switches to the reloaded settings `new`. The view only starts over if
+/// its analysis changed, and the source is switched if it changed. Fails
+/// if the new source couldn't be opened, leaving the old one in place.
+fn apply(
+    new: Settings,
+    settings: &mut Settings,
+    source: &mut Box<dyn AudioSource>,
+    decoder: &mut Option<Decoder>,
+    terminal: &mut Terminal,
+    view: &mut ViewState,
+) -> Result<(), Error> {
+    view.update(settings, &new);
+    if new.display.color != settings.display.color {
+        terminal.set_color(new.display.color);
+    }
+    settings.display = new.display;
+    settings.analysis = new.analysis;
+
+    if new.source == settings.source {
+        return Ok(());
+    }
+    // A source that can't switch in place is replaced. Until the new one
+    // is ready, nothing is decoded.
+    if !source.reconfigure(&new.source) {
+        *source = new.source.clone().open()?;
+    }
+    *decoder = None;
+    settings.source = new.source;
+    Ok(())
+}
+
+/// The analysis behind the selected view, fed with every decoded block.
+enum ViewState {
+    Bars(BarLevels),
+    Scope(WaveformBuffer),
+    Vector(WaveformBuffer, CorrelationMeter),
+    Meters(LevelMeter),
+    Spectrogram(SpectrumAnalyzer, Spectrogram),
+}
+
+impl ViewState {
+    fn new(display: &DisplayConfig, analysis: &AnalysisConfig) -> Self {
+        match display.view {
+            View::Bars => ViewState::Bars(BarLevels::new(
+                analysis.spectrum,
+                analysis.bands,
+                display.smoothing,
+            )),
+            View::Scope => ViewState::Scope(WaveformBuffer::new(display.scope_window)),
+            View::Vector => ViewState::Vector(
+                WaveformBuffer::new(display.scope_window),
+                CorrelationMeter::new(CORRELATION_INTEGRATION),
+            ),
+            View::Meters => ViewState::Meters(LevelMeter::new(analysis.ballistics)),
+            View::Spectrogram => ViewState::Spectrogram(
+                SpectrumAnalyzer::new(analysis.spectrum),
+                Spectrogram::new(display.spectrogram, &analysis.bands),
+            ),
+        }
+    }
+
+    /// Switches from the `old` settings to the `new` ones. An analysis set
+    /// up differently starts over, while what's only shown differently
+    /// keeps the history, smoothing and held peaks.
+    fn update(&mut self, old: &Settings, new: &Settings) {
+        let (before, after) = (&old.analysis, &new.analysis);
+        let spectrum_changed = after.spectrum != before.spectrum || after.bands != before.bands;
+        let rebuild = new.display.view != old.display.view
+            || match new.display.view {
+                View::Bars => spectrum_changed,
+                View::Scope | View::Vector => new.display.scope_window != old.display.scope_window,
+                View::Meters => after.ballistics != before.ballistics,
+                View::Spectrogram => {
+                    spectrum_changed
+                        || new.display.spectrogram.scale != old.display.spectrogram.scale
+                }
+            };
+
+        if rebuild {
+            log::debug!(
+                target: logging::ANALYSIS,
+                "Starting the {:?} view over.",
+                new.display.view
+            );
+            *self = ViewState::new(&new.display, after);
+            return;
+        }
+        match self {
+            ViewState::Bars(levels) => levels.smoothing = new.display.smoothing,
+            ViewState::Spectrogram(_, spectrogram) => {
+                spectrogram.set_colors(new.display.spectrogram)
+            }
+            _ => {}
+        }
+    }
+
+    fn push(&mut self, block: &AudioBlock) {
+        match self {
+            ViewState::Bars(levels) => levels.push(block),
+            ViewState::Scope(waveform) => waveform.push(block),
+            ViewState::Vector(waveform, correlation) => {
+                waveform.push(block);
+                correlation.push(block);
+            }
+            ViewState::Meters(meter) => meter.push(block),
+            ViewState::Spectrogram(analyzer, spectrogram) => {
+                analyzer.push(block, |spectrum| spectrogram.push(spectrum))
+            }
+        }
+    }
+
+    fn draw(&self, screen: &mut Screen, area: Rect, display: &DisplayConfig) {
+        match self {
+            ViewState::Bars(levels) => bars::draw(screen, area, &levels.levels, &Gradient::LEVEL),
+            ViewState::Scope(waveform) => {
+                // Both traces trigger on the left channel, so they stay in
+                // phase with each other.
+                let pair = analysis::stereo_pair(waveform.positions());
+                let window = waveform.triggered(pair.map_or(0, |(left, _)| left));
+                let traces: Vec<_> = match pair {
+                    Some((left, right)) => {
+                        vec![(window[left], scope::LEFT), (window[right], scope::RIGHT)]
+                    }
+                    None => window
+                        .first()
+                        .map(|&mono| (mono, scope::LEFT))
+                        .into_iter()
+                        .collect(),
+                };
+                scope::draw(screen, area, &traces, display.scope_layout);
+            }
+            ViewState::Vector(waveform, correlation) => {
+                let window = waveform.latest();
+                let (left, right) = match analysis::stereo_pair(waveform.positions()) {
+                    Some((left, right)) => (window[left], window[right]),
+                    None => match window.first() {
+                        Some(&mono) => (mono, mono),
+                        None => return,
+                    },
+                };
+                vector::draw(screen, area, left, right, correlation.value());
+            }
+            ViewState::Meters(meter) => meters::draw(screen, area, meter.levels()),
+            ViewState::Spectrogram(_, spectrogram) => spectrogram.draw(screen, area),
+        }
+    }
+}
+
+/// Turns decoded audio into bar heights in 0.0..=1.0, one per band of the
+/// latest spectrum.
+struct BarLevels {
+    analyzer: SpectrumAnalyzer,
+    mapper: BandMapper,
+    floor: f32,
+    /// How much of the previous heights carries over, in 0.0..1.0.
+    smoothing: f32,
+    levels: Vec<f32>,
+}
+
+impl BarLevels {
+    fn new(spectrum: SpectrumConfig, bands: BandConfig, smoothing: f32) -> Self {
+        BarLevels {
+            analyzer: SpectrumAnalyzer::new(spectrum),
+            mapper: BandMapper::new(bands),
+            floor: spectrum.db_floor,
+            smoothing,
+            levels: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, block: &AudioBlock) {
+        let BarLevels {
+            analyzer,
+            mapper,
+            floor,
+            smoothing,
+            levels,
+        } = self;
+
+        analyzer.push(block, |spectrum| {
+            let heights = mapper
+                .map(spectrum)
+                .into_iter()
+                .map(|magnitude| (spectrum::to_db(magnitude, *floor) - *floor) / -*floor);
+            if levels.len() != heights.len() {
+                levels.clear();
+                levels.extend(heights);
+                return;
+            }
+            for (level, height) in levels.iter_mut().zip(heights) {
+                *level = height + (*level - height) * *smoothing;
+            }
+        });
+    }
+}
+
+/// The loudness meter that runs whatever the view, and where its readings
+/// are logged.
+struct Loudness {
+    meter: LoudnessMeter,
+    log: Option<(PathBuf, BufWriter<File>)>,
+}
+
+impl Loudness {
+    fn new(target: f64, log: Option<PathBuf>) -> Result<Self, Error> {
+        let log = match log {
+            Some(path) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&path)
+                    .map_err(|err| Error::Output(path.clone(), err))?;
+                Some((path, BufWriter::new(file)))
+            }
+            None => None,
+        };
+
+        Ok(Loudness {
+            meter: LoudnessMeter::new(target),
+            log,
+        })
+    }
+
+    fn push(&mut self, block: &AudioBlock) -> Result<(), Error> {
+        let mut result = Ok(());
+        let log = &mut self.log;
+        self.meter.push(block, |meter| {
+            let Some((_, writer)) = log else {
+                return;
+            };
+            if result.is_ok() {
+                result = serde_json::to_writer(&mut *writer, &meter.reading())
+                    .map_err(io::Error::from)
+                    .and_then(|()| writeln!(writer));
+            }
+        });
+
+        match (result, &self.log) {
+            (Err(err), Some((path, _))) => Err(Error::Output(path.clone(), err)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Flushes the log and returns the summary of the whole run.
+    fn finish(self) -> Result<LoudnessSummary, Error> {
+        if let Some((path, mut writer)) = self.log {
+            writer.flush().map_err(|err| Error::Output(path, err))?;
+        }
+
+        Ok(self.meter.summary())
+    }
+}
+
+/// Draws `view` above a status line, with the loudness on the right.
+fn draw(
+    terminal: &mut Terminal,
+    view: &ViewState,
+    display: &DisplayConfig,
+    status: &str,
+    loudness: &LoudnessReading,
+) -> Result<(), Error> {
+    let (width, height) = terminal.size().map_err(Error::Terminal)?;
+    let mut screen = Screen::new(width, height);
+
+    let mut area = screen.area();
+    area.height = area.height.saturating_sub(1);
+    view.draw(&mut screen, area, display);
+    screen.print(0, area.height, status, None);
+
+    let value = |value: Option<f64>| value.map_or("-".to_string(), |value| format!("{:.1}", value));
+    let loudness = format!(
+        " M {}  S {}  I {} LUFS",
+        value(loudness.momentary),
+        value(loudness.short_term),
+        value(loudness.integrated)
+    );
+    let x = width.saturating_sub(loudness.chars().count() as u16);
+    screen.print(x, area.height, &loudness, None);
+
+    terminal.draw(&screen).map_err(Error::Terminal)
+}
@@ -0,0 +1,393 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use pulse::{
+    channelmap::{Map, MapDef, Position},
+    sample::{Format, Spec},
+};
+
+use crate::error::Error;
+
+/// Largest data chunk a RIFF file can describe. Files are split before
+/// reaching it.
+pub const MAX_DATA_LEN: u64 = u32::MAX as u64 - 1024;
+
+/// The `KSDATAFORMAT_SUBTYPE_*` GUID tail shared by PCM and float.
+const SUBFORMAT_TAIL: [u8; 14] = [
+    0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xaa, 0x00, 0x38, 0x9b, 0x71,
+];
+
+const FORMAT_PCM: u16 = 1;
+const FORMAT_FLOAT: u16 = 3;
+const FORMAT_EXTENSIBLE: u16 = 0xfffe;
+/// Size of a `WAVE_FORMAT_EXTENSIBLE` fmt chunk.
+const EXTENSIBLE_FMT_LEN: u64 = 40;
+
+/// The speakers of the `WAVEFORMATEXTENSIBLE` channel mask bits, lowest bit
+/// first.
+const SPEAKERS: [Position; 18] = [
+    Position::FrontLeft,
+    Position::FrontRight,
+    Position::FrontCenter,
+    Position::Lfe,
+    Position::RearLeft,
+    Position::RearRight,
+    Position::FrontLeftOfCenter,
+    Position::FrontRightOfCenter,
+    Position::RearCenter,
+    Position::SideLeft,
+    Position::SideRight,
+    Position::TopCenter,
+    Position::TopFrontLeft,
+    Position::TopFrontCenter,
+    Position::TopFrontRight,
+    Position::TopRearLeft,
+    Position::TopRearCenter,
+    Position::TopRearRight,
+];
+
+/// The `WAVEFORMATEXTENSIBLE` speaker bit of `position`, if it has one.
+fn speaker_mask(position: Position) -> Option<u32> {
+    let position = match position {
+        Position::Mono => Position::FrontCenter,
+        position => position,
+    };
+    SPEAKERS
+        .iter()
+        .position(|&speaker| speaker == position)
+        .map(|bit| 1 << bit)
+}
+
+/// The channel mask for `map`. WAV files order channels by speaker bit, so
+/// a map in any other order, or with positions WAV has no bit for, gets an
+/// empty mask, which leaves the layout unspecified.
+fn channel_mask(map: &Map) -> u32 {
+    let mut mask = 0;
+    for &position in map.get() {
+        match speaker_mask(position) {
+            Some(bit) if bit > mask => mask |= bit,
+            _ => return 0,
+        }
+    }
+    mask
+}
+
+/// The channel map for `channels` channels with speakers `mask`. Without a
+/// usable mask, mono is mono and anything else gets the default order of
+/// `WAVEFORMATEXTENSIBLE`.
+fn channel_map(channels: u8, mask: u32) -> Map {
+    let mut map = Map::default();
+    if mask.count_ones() == channels as u32 && mask >> SPEAKERS.len() == 0 {
+        map.set_len(channels);
+        let speakers = (0..SPEAKERS.len())
+            .filter(|bit| mask & (1 << bit) != 0)
+            .map(|bit| SPEAKERS[bit]);
+        for (position, speaker) in map.get_mut().iter_mut().zip(speakers) {
+            *position = speaker;
+        }
+    } else if channels == 1 {
+        map.init_mono();
+    } else {
+        map.init_extend(channels, MapDef::WAVEEx);
+    }
+    map
+}
+
+/// A WAV file being written. The RIFF and data chunk sizes are written as
+/// zero at first and patched by [`WavWriter::finish`].
+pub struct WavWriter {
+    path: PathBuf,
+    file: BufWriter<File>,
+    /// Offset of the data chunk's size field.
+    data_size_offset: u64,
+    data_len: u64,
+}
+
+impl WavWriter {
+    /// Creates `path` with a header for audio in `spec` laid out as `map`.
+    /// Formats with more than two channels or more than 16 bits use
+    /// `WAVE_FORMAT_EXTENSIBLE`, as the format's spec asks, so players know
+    /// which speaker each channel is for and how many bits are valid.
+    pub fn create(path: &Path, spec: &Spec, map: &Map) -> Result<WavWriter, Error> {
+        let output_error = |err| Error::Output(path.to_path_buf(), err);
+        let file = File::create(path).map_err(output_error)?;
+
+        let mut writer = WavWriter {
+            path: path.to_path_buf(),
+            file: BufWriter::new(file),
+            data_size_offset: 0,
+            data_len: 0,
+        };
+        writer.write_header(spec, map).map_err(output_error)?;
+        Ok(writer)
+    }
+
+    fn write_header(&mut self, spec: &Spec, map: &Map) -> io::Result<()> {
+        let format = if spec.format == Format::F32le {
+            FORMAT_FLOAT
+        } else {
+            FORMAT_PCM
+        };
+        let bits = (spec.sample_size() * 8) as u16;
+        let block_align = spec.frame_size() as u16;
+        let extensible = spec.channels > 2 || bits > 16;
+
+        let file = &mut self.file;
+        file.write_all(b"RIFF")?;
+        file.write_all(&0u32.to_le_bytes())?;
+        file.write_all(b"WAVE")?;
+
+        file.write_all(b"fmt ")?;
+        file.write_all(
+            &(if extensible {
+                EXTENSIBLE_FMT_LEN as u32
+            } else {
+                16
+            })
+            .to_le_bytes(),
+        )?;
+        file.write_all(
+            &(if extensible {
+                FORMAT_EXTENSIBLE
+            } else {
+                format
+            })
+            .to_le_bytes(),
+        )?;
+        file.write_all(&(spec.channels as u16).to_le_bytes())?;
+        file.write_all(&spec.rate.to_le_bytes())?;
+        file.write_all(&(spec.rate * block_align as u32).to_le_bytes())?;
+        file.write_all(&block_align.to_le_bytes())?;
+        file.write_all(&bits.to_le_bytes())?;
+        if extensible {
+            file.write_all(&22u16.to_le_bytes())?;
+            file.write_all(&bits.to_le_bytes())?;
+            file.write_all(&channel_mask(map).to_le_bytes())?;
+            file.write_all(&format.to_le_bytes())?;
+            file.write_all(&SUBFORMAT_TAIL)?;
+        }
+
+        file.write_all(b"data")?;
+        self.data_size_offset = file.stream_position()?;
+        file.write_all(&0u32.to_le_bytes())?;
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Bytes of audio written so far.
+    pub fn data_len(&self) -> u64 {
+        self.data_len
+    }
+
+    pub fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.file
+            .write_all(data)
+            .map_err(|err| Error::Output(self.path.clone(), err))?;
+        self.data_len += data.len() as u64;
+        Ok(())
+    }
+
+    /// Pads the data chunk to an even length as RIFF requires, writes the
+    /// final sizes into the header and closes the file.
+    pub fn finish(mut self) -> Result<(), Error> {
+        self.patch_sizes()
+            .map_err(|err| Error::Output(self.path.clone(), err))
+    }
+
+    fn patch_sizes(&mut self) -> io::Result<()> {
+        if self.data_len % 2 == 1 {
+            self.file.write_all(&[0])?;
+        }
+        let riff_len = self.data_size_offset + 4 + self.data_len + self.data_len % 2 - 8;
+
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file.write_all(&(riff_len as u32).to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(self.data_size_offset))?;
+        self.file.write_all(&(self.data_len as u32).to_le_bytes())?;
+        self.file.flush()
+    }
+}
+
+/// A WAV file being read, positioned in its data chunk.
+///
+/// Reads 8, 16, 24 and 32 bit PCM and 32 bit float, plain or as
+/// `WAVE_FORMAT_EXTENSIBLE`. A data chunk with a size of zero or one that
+/// runs past the end of the file, as left behind by a recording that was
+/// killed, is read up to the end of the file.
+pub struct WavReader {
+    path: PathBuf,
+    file: BufReader<File>,
+    spec: Spec,
+    map: Map,
+    /// Bytes of audio left in the data chunk.
+    remaining: u64,
+}
+
+impl WavReader {
+    pub fn open(path: &Path) -> Result<WavReader, Error> {
+        let input_error = |err| Error::Input(path.to_path_buf(), err);
+        let file = File::open(path).map_err(input_error)?;
+        let len = file.metadata().map_err(input_error)?.len();
+
+        let mut file = BufReader::new(file);
+        let (spec, map, remaining) = read_header(&mut file, len).map_err(input_error)?;
+        Ok(WavReader {
+            path: path.to_path_buf(),
+            file,
+            spec,
+            map,
+            remaining,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn spec(&self) -> Spec {
+        self.spec
+    }
+
+    pub fn channel_map(&self) -> Map {
+        self.map
+    }
+
+    /// Reads audio into `buf`, returning how many bytes were read, or 0 at
+    /// the end of the data.
+    pub fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let len = (buf.len() as u64).min(self.remaining) as usize;
+        let read = self
+            .file
+            .read(&mut buf[..len])
+            .map_err(|err| Error::Input(self.path.clone(), err))?;
+        self.remaining -= read as u64;
+        if read == 0 {
+            self.remaining = 0;
+        }
+        Ok(read)
+    }
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message.into())
+}
+
+fn read_array<const N: usize>(file: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    file.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+/// Reads chunks up to the start of the audio data. Returns the format and
+/// the length of the data. `len` is the length of the whole file.
+fn read_header(file: &mut BufReader<File>, len: u64) -> io::Result<(Spec, Map, u64)> {
+    let riff = read_array::<12>(file)?;
+    if &riff[..4] != b"RIFF" || &riff[8..] != b"WAVE" {
+        return Err(invalid("not a WAV file"));
+    }
+
+    let mut format = None;
+    loop {
+        let header = match read_array::<8>(file) {
+            Ok(header) => header,
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => {
+                return Err(invalid("no data chunk"))
+            }
+            Err(err) => return Err(err),
+        };
+        let size = u32_at(&header, 4) as u64;
+
+        match &header[..4] {
+            b"fmt " => {
+                // Nothing past the extensible fields is needed, so a bogus
+                // size can't make this allocate much.
+                let mut chunk = vec![0; size.min(EXTENSIBLE_FMT_LEN) as usize];
+                file.read_exact(&mut chunk)?;
+                format = Some(parse_format(&chunk)?);
+                file.seek_relative((size - chunk.len() as u64 + size % 2) as i64)?;
+            }
+            b"data" => {
+                let Some((spec, map)) = format else {
+                    return Err(invalid("data chunk before the fmt chunk"));
+                };
+                let available = len.saturating_sub(file.stream_position()?);
+                let size = if size == 0 || size > available {
+                    available
+                } else {
+                    size
+                };
+                return Ok((spec, map, size));
+            }
+            _ => file.seek_relative((size + size % 2) as i64)?,
+        }
+    }
+}
+
+/// Parses the body of a fmt chunk.
+fn parse_format(chunk: &[u8]) -> io::Result<(Spec, Map)> {
+    if chunk.len() < 16 {
+        return Err(invalid("fmt chunk too short"));
+    }
+
+    let mut tag = u16_at(chunk, 0);
+    let channels = u16_at(chunk, 2);
+    let rate = u32_at(chunk, 4);
+    let block_align = u16_at(chunk, 12);
+    let bits = u16_at(chunk, 14);
+
+    let mut mask = 0;
+    if tag == FORMAT_EXTENSIBLE {
+        if chunk.len() < EXTENSIBLE_FMT_LEN as usize {
+            return Err(invalid("extensible fmt chunk too short"));
+        }
+        mask = u32_at(chunk, 20);
+        tag = u16_at(chunk, 24);
+    }
+
+    let format = match (tag, bits) {
+        (FORMAT_PCM, 8) => Format::U8,
+        (FORMAT_PCM, 16) => Format::S16le,
+        (FORMAT_PCM, 24) => Format::S24le,
+        (FORMAT_PCM, 32) => Format::S32le,
+        (FORMAT_FLOAT, 32) => Format::F32le,
+        _ => {
+            return Err(invalid(format!(
+                "unsupported format {:#06x} with {} bit samples",
+                tag, bits
+            )))
+        }
+    };
+
+    let spec = Spec {
+        format,
+        channels: u8::try_from(channels).unwrap_or(0),
+        rate,
+    };
+    if !spec.is_valid() || block_align as usize != spec.frame_size() {
+        return Err(invalid(format!(
+            "{} channels of {} bit samples at {} Hz is not a valid format",
+            channels, bits, rate
+        )));
+    }
+
+    Ok((spec, channel_map(spec.channels, mask)))
+}
@@ -0,0 +1,420 @@
+mod common;
+
+use common::{
+    collect, config, following_config, reconnecting_config, sink, FakeConnector, ServerScript, SPEC,
+};
+use pulse::error::{Code, PAErr};
+use pulse_visualizer::{
+    capture::{Capture, CaptureEvent},
+    decode::Decoder,
+    error::Error,
+    server::{Peek, StreamState},
+};
+
+fn is_ready(event: &CaptureEvent) -> bool {
+    matches!(event, CaptureEvent::Ready { .. })
+}
+
+fn is_switched(event: &CaptureEvent) -> bool {
+    matches!(event, CaptureEvent::Switched { .. })
+}
+
+fn ended(events: &[CaptureEvent]) -> bool {
+    matches!(events.last(), Some(CaptureEvent::Failed(_)))
+}
+
+/// Speakers as the default sink, and headphones.
+fn two_sinks() -> ServerScript {
+    ServerScript {
+        sinks: vec![sink(0, "speakers"), sink(1, "headphones")],
+        default_sink: Some("speakers".to_string()),
+        ..ServerScript::default()
+    }
+}
+
+#[test]
+fn records_the_selected_sinks_monitor() {
+    let script = ServerScript {
+        sinks: vec![sink(0, "speakers"), sink(1, "headphones")],
+        default_sink: Some("speakers".to_string()),
+        ..ServerScript::default()
+    };
+    let mut capture = Capture::start_with(FakeConnector::new(vec![Ok(script)]), config("1"));
+
+    let events = collect(&mut capture, |events| !events.is_empty());
+    match events.as_slice() {
+        [CaptureEvent::Ready {
+            sink,
+            source,
+            spec,
+            channel_map,
+        }] => {
+            assert_eq!(sink, "headphones");
+            assert_eq!(source, "headphones.monitor");
+            assert_eq!(*spec, SPEC);
+            assert_eq!(channel_map.get().len(), 2);
+        }
+        other => panic!("expected Ready, got {:?}", other),
+    }
+}
+
+#[test]
+fn unknown_device_fails() {
+    let connector = FakeConnector::new(vec![Ok(ServerScript::with_sink("speakers"))]);
+    let mut capture = Capture::start_with(connector, config("headphones"));
+
+    let events = collect(&mut capture, ended);
+    assert!(matches!(
+        events.as_slice(),
+        [CaptureEvent::Failed(Error::NoDevice(_))]
+    ));
+}
+
+#[test]
+fn connect_failure_is_fatal_before_ready() {
+    let refused = PAErr::from(Code::ConnectionRefused);
+    // Reconnecting is enabled, but never having been ready means the
+    // server or device is wrong rather than gone.
+    let mut capture = Capture::start_with(
+        FakeConnector::new(vec![Err(refused)]),
+        reconnecting_config(),
+    );
+
+    let events = collect(&mut capture, ended);
+    match events.as_slice() {
+        [CaptureEvent::Failed(Error::Connection(err))] => assert_eq!(*err, refused),
+        other => panic!("expected a connection failure, got {:?}", other),
+    }
+}
+
+#[test]
+fn record_stream_creation_failure() {
+    let script = ServerScript {
+        record_error: Some(PAErr::from(Code::NoEntity)),
+        ..ServerScript::with_sink("speakers")
+    };
+    let mut capture = Capture::start_with(FakeConnector::new(vec![Ok(script)]), config("speakers"));
+
+    let events = collect(&mut capture, ended);
+    assert!(matches!(
+        events.as_slice(),
+        [CaptureEvent::Failed(Error::Stream(_))]
+    ));
+}
+
+#[test]
+fn stream_failing_while_connecting() {
+    let killed = PAErr::from(Code::Killed);
+    let script = ServerScript {
+        states: vec![
+            StreamState::Connecting,
+            StreamState::Connecting,
+            StreamState::Failed(killed),
+        ],
+        ..ServerScript::with_sink("speakers")
+    };
+    let mut capture = Capture::start_with(FakeConnector::new(vec![Ok(script)]), config("speakers"));
+
+    let events = collect(&mut capture, ended);
+    match events.as_slice() {
+        [CaptureEvent::Failed(Error::Stream(err))] => assert_eq!(*err, killed),
+        other => panic!("expected a stream failure, got {:?}", other),
+    }
+}
+
+#[test]
+fn stream_failure_without_reconnect() {
+    let script = ServerScript {
+        states: vec![
+            StreamState::Ready,
+            StreamState::Failed(PAErr::from(Code::Killed)),
+        ],
+        ..ServerScript::with_sink("speakers")
+    };
+    let mut capture = Capture::start_with(FakeConnector::new(vec![Ok(script)]), config("speakers"));
+
+    let events = collect(&mut capture, ended);
+    assert!(matches!(
+        events.as_slice(),
+        [
+            CaptureEvent::Ready { .. },
+            CaptureEvent::Failed(Error::Stream(_))
+        ]
+    ));
+}
+
+#[test]
+fn stream_failure_reconnects() {
+    let failing = ServerScript {
+        states: vec![
+            StreamState::Ready,
+            StreamState::Failed(PAErr::from(Code::Killed)),
+        ],
+        ..ServerScript::with_sink("speakers")
+    };
+    let connector = FakeConnector::new(vec![Ok(failing), Ok(ServerScript::with_sink("speakers"))]);
+    let mut capture = Capture::start_with(connector, reconnecting_config());
+
+    let events = collect(&mut capture, |events| {
+        events.iter().filter(|event| is_ready(event)).count() == 2
+    });
+    match events.as_slice() {
+        [CaptureEvent::Ready { .. }, CaptureEvent::Reconnecting {
+            error: Error::Stream(_),
+            attempt: 1,
+            ..
+        }, CaptureEvent::Ready { sink, .. }] => assert_eq!(sink, "speakers"),
+        other => panic!("expected a reconnect, got {:?}", other),
+    }
+}
+
+#[test]
+fn lost_server_keeps_reconnecting() {
+    let script = ServerScript {
+        lifetime: Some(3),
+        ..ServerScript::with_sink("speakers")
+    };
+    // After the first connection goes away, connecting is refused, which
+    // keeps being retried.
+    let mut capture =
+        Capture::start_with(FakeConnector::new(vec![Ok(script)]), reconnecting_config());
+
+    let events = collect(&mut capture, |events| events.len() >= 4);
+    assert!(is_ready(&events[0]));
+    assert!(matches!(
+        events[1],
+        CaptureEvent::Reconnecting {
+            error: Error::Disconnected,
+            attempt: 1,
+            ..
+        }
+    ));
+    assert!(matches!(
+        events[2],
+        CaptureEvent::Reconnecting {
+            error: Error::Connection(_),
+            attempt: 2,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn forwards_data_and_holes_in_order() {
+    let script = ServerScript {
+        reads: vec![
+            Ok(Peek::Data(vec![1, 0, 2, 0])),
+            Ok(Peek::Hole(8)),
+            Ok(Peek::Empty),
+            Ok(Peek::Data(vec![3, 0, 4, 0])),
+        ],
+        ..ServerScript::with_sink("speakers")
+    };
+    let mut capture = Capture::start_with(FakeConnector::new(vec![Ok(script)]), config("speakers"));
+
+    let events = collect(&mut capture, |events| events.len() >= 4);
+    match events.as_slice() {
+        [CaptureEvent::Ready { .. }, CaptureEvent::Data(first), CaptureEvent::Hole(8), CaptureEvent::Data(second)] =>
+        {
+            assert_eq!(first, &[1, 0, 2, 0]);
+            assert_eq!(second, &[3, 0, 4, 0]);
+        }
+        other => panic!("expected data around a hole, got {:?}", other),
+    }
+}
+
+#[test]
+fn read_error_fails_the_stream() {
+    let script = ServerScript {
+        reads: vec![Ok(Peek::Data(vec![0; 4])), Err(PAErr::from(Code::IO))],
+        ..ServerScript::with_sink("speakers")
+    };
+    let mut capture = Capture::start_with(FakeConnector::new(vec![Ok(script)]), config("speakers"));
+
+    let events = collect(&mut capture, ended);
+    assert!(matches!(
+        events.as_slice(),
+        [
+            CaptureEvent::Ready { .. },
+            CaptureEvent::Data(_),
+            CaptureEvent::Failed(Error::Stream(_))
+        ]
+    ));
+}
+
+#[test]
+fn holes_decode_to_silence_of_the_same_length() {
+    let script = ServerScript {
+        reads: vec![
+            Ok(Peek::Data(vec![0x00, 0x40, 0x00, 0xc0])),
+            Ok(Peek::Hole(8)),
+            Ok(Peek::Data(vec![0x00, 0x40, 0x00, 0xc0])),
+        ],
+        ..ServerScript::with_sink("speakers")
+    };
+    let mut capture = Capture::start_with(FakeConnector::new(vec![Ok(script)]), config("speakers"));
+
+    let events = collect(&mut capture, |events| events.len() >= 4);
+    let mut decoder = None;
+    let mut left = Vec::new();
+    for event in events {
+        let block = match event {
+            CaptureEvent::Ready {
+                spec, channel_map, ..
+            } => {
+                decoder = Some(Decoder::new(&spec, &channel_map).unwrap());
+                continue;
+            }
+            CaptureEvent::Data(data) => decoder.as_mut().unwrap().decode(&data),
+            CaptureEvent::Hole(len) => decoder.as_mut().unwrap().decode_hole(len),
+            other => panic!("unexpected {:?}", other),
+        };
+        assert_eq!(block.channels.len(), 2);
+        left.extend_from_slice(&block.channels[0]);
+    }
+
+    // One frame, two silent frames for the 8 byte hole, one frame.
+    assert_eq!(left, [0.5, 0.0, 0.0, 0.5]);
+}
+
+#[test]
+fn reconfiguring_restarts_the_stream_on_the_same_connection() {
+    let script = ServerScript {
+        sinks: vec![sink(0, "speakers"), sink(1, "headphones")],
+        default_sink: Some("speakers".to_string()),
+        ..ServerScript::default()
+    };
+    // Connecting a second time would be refused, and reconnecting is off.
+    let mut capture = Capture::start_with(FakeConnector::new(vec![Ok(script)]), config("speakers"));
+
+    let events = collect(&mut capture, |events| !events.is_empty());
+    assert!(matches!(events.as_slice(), [CaptureEvent::Ready { .. }]));
+
+    capture.reconfigure(config("headphones"));
+    let events = collect(&mut capture, |events| !events.is_empty());
+    match events.as_slice() {
+        [CaptureEvent::Ready { sink, source, .. }] => {
+            assert_eq!(sink, "headphones");
+            assert_eq!(source, "headphones.monitor");
+        }
+        other => panic!("expected Ready on the new sink, got {:?}", other),
+    }
+}
+
+#[test]
+fn follows_the_default_sink() {
+    let script = ServerScript {
+        default_sinks: vec![(3, Some("headphones".to_string()))],
+        ..two_sinks()
+    };
+    let moves = script.moves.clone();
+    let mut capture = Capture::start_with(
+        FakeConnector::new(vec![Ok(script)]),
+        following_config("@DEFAULT_MONITOR@"),
+    );
+
+    let events = collect(&mut capture, |events| events.iter().any(is_switched));
+    match events.as_slice() {
+        [CaptureEvent::Ready { sink: first, .. }, CaptureEvent::Switched { sink, source }] => {
+            assert_eq!(first, "speakers");
+            assert_eq!(sink, "headphones");
+            assert_eq!(source, "headphones.monitor");
+        }
+        other => panic!("expected a switch to the headphones, got {:?}", other),
+    }
+    assert_eq!(
+        *moves.lock().unwrap(),
+        [(0, "headphones.monitor".to_string())]
+    );
+}
+
+#[test]
+fn default_sink_that_is_already_gone_is_skipped() {
+    let script = ServerScript {
+        default_sinks: vec![
+            (3, Some("unplugged".to_string())),
+            (6, Some("headphones".to_string())),
+        ],
+        ..two_sinks()
+    };
+    let moves = script.moves.clone();
+    let mut capture = Capture::start_with(
+        FakeConnector::new(vec![Ok(script)]),
+        following_config("@DEFAULT_MONITOR@"),
+    );
+
+    let events = collect(&mut capture, |events| events.iter().any(is_switched));
+    assert!(matches!(
+        events.as_slice(),
+        [CaptureEvent::Ready { .. }, CaptureEvent::Switched { sink, .. }] if sink == "headphones"
+    ));
+    assert_eq!(
+        *moves.lock().unwrap(),
+        [(0, "headphones.monitor".to_string())]
+    );
+}
+
+#[test]
+fn reconnecting_after_a_switch_resolves_the_default_sink() {
+    let switching = ServerScript {
+        default_sinks: vec![(3, Some("headphones".to_string()))],
+        lifetime: Some(6),
+        ..two_sinks()
+    };
+    let restarted = ServerScript {
+        default_sink: Some("headphones".to_string()),
+        ..two_sinks()
+    };
+    // The selector names the speakers, which only the first connect uses.
+    let connector = FakeConnector::new(vec![Ok(switching), Ok(restarted)]);
+    let mut capture = Capture::start_with(connector, following_config("speakers"));
+
+    let events = collect(&mut capture, |events| {
+        events.iter().filter(|event| is_ready(event)).count() == 2
+    });
+    match events.as_slice() {
+        [CaptureEvent::Ready { sink: first, .. }, CaptureEvent::Switched { .. }, CaptureEvent::Reconnecting { .. }, CaptureEvent::Ready { sink, .. }] =>
+        {
+            assert_eq!(first, "speakers");
+            assert_eq!(sink, "headphones");
+        }
+        other => panic!("expected to reconnect to the headphones, got {:?}", other),
+    }
+}
+
+#[test]
+fn reconfiguring_to_an_unknown_sink_keeps_the_stream() {
+    // One fragment per mainloop iteration, for a while.
+    let reads = (0..1000)
+        .flat_map(|_| [Ok(Peek::Data(vec![0; 4])), Ok(Peek::Empty)])
+        .collect();
+    let script = ServerScript {
+        reads,
+        ..ServerScript::with_sink("speakers")
+    };
+    let mut capture = Capture::start_with(FakeConnector::new(vec![Ok(script)]), config("speakers"));
+
+    let events = collect(&mut capture, |events| !events.is_empty());
+    assert!(matches!(events.as_slice(), [CaptureEvent::Ready { .. }]));
+
+    capture.reconfigure(config("headphones"));
+    let events = collect(&mut capture, |events| {
+        let rejected = events
+            .iter()
+            .position(|event| matches!(event, CaptureEvent::Rejected(_)));
+        rejected.is_some_and(|rejected| events.len() > rejected + 3)
+    });
+    let others: Vec<&CaptureEvent> = events
+        .iter()
+        .filter(|event| !matches!(event, CaptureEvent::Data(_)))
+        .collect();
+    assert!(
+        matches!(
+            others.as_slice(),
+            [CaptureEvent::Rejected(Error::NoDevice(_))]
+        ),
+        "expected only the rejection besides data, got {:?}",
+        others
+    );
+    assert!(matches!(events.last(), Some(CaptureEvent::Data(_))));
+}
@@ -0,0 +1,273 @@
+//! A scripted in-memory sound server for driving capture without PulseAudio.
+
+#![allow(dead_code)]
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    sync::{mpsc::RecvTimeoutError, Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use pulse::{
+    channelmap::Map,
+    error::{Code, PAErr},
+    sample::{Format, Spec},
+};
+use pulse_visualizer::{
+    capture::{CaptureConfig, CaptureEvent, ReconnectPolicy},
+    device::{DeviceSelector, SampleSpec, ServerDefaults, Sink, Source},
+    error::Error,
+    server::{Connector, Peek, RecordStream, Server, StreamState},
+    source::AudioSource,
+    spec::SpecRequest,
+};
+
+/// The format every fake sink has.
+pub const SPEC: Spec = Spec {
+    format: Format::S16le,
+    channels: 2,
+    rate: 44100,
+};
+
+/// A stereo sink named `name` with a monitor source `<name>.monitor`.
+pub fn sink(index: u32, name: &str) -> Sink {
+    let mut map = Map::default();
+    map.init_stereo();
+    Sink {
+        index,
+        name: name.to_string(),
+        description: String::new(),
+        sample_spec: SampleSpec::from(&SPEC),
+        channel_map: vec!["front-left".to_string(), "front-right".to_string()],
+        monitor_source_name: Some(format!("{}.monitor", name)),
+        native_spec: SPEC,
+        native_channel_map: map,
+    }
+}
+
+/// Capture of `selector` that gives up on the first error.
+pub fn config(selector: &str) -> CaptureConfig {
+    CaptureConfig {
+        selector: selector.parse().unwrap(),
+        spec: SpecRequest::default(),
+        reconnect: ReconnectPolicy {
+            enabled: false,
+            ..ReconnectPolicy::default()
+        },
+        follow_default: false,
+    }
+}
+
+/// Capture of the default sink that reconnects right away.
+pub fn reconnecting_config() -> CaptureConfig {
+    CaptureConfig {
+        selector: DeviceSelector::DefaultMonitor,
+        reconnect: ReconnectPolicy {
+            enabled: true,
+            initial_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(1),
+        },
+        ..config("@DEFAULT_MONITOR@")
+    }
+}
+
+/// Capture of `selector` that follows the default sink and reconnects
+/// right away.
+pub fn following_config(selector: &str) -> CaptureConfig {
+    CaptureConfig {
+        selector: selector.parse().unwrap(),
+        follow_default: true,
+        ..reconnecting_config()
+    }
+}
+
+/// What one connection to the fake server does.
+#[derive(Debug, Clone, Default)]
+pub struct ServerScript {
+    pub sinks: Vec<Sink>,
+    pub default_sink: Option<String>,
+    /// The stream's state each time it's checked. The last one sticks, and
+    /// an empty list means ready right away.
+    pub states: Vec<StreamState>,
+    /// What each read of a ready stream returns, then `Empty` forever.
+    pub reads: Vec<Result<Peek, PAErr>>,
+    /// Creating the record stream fails with this.
+    pub record_error: Option<PAErr>,
+    /// The server goes away after this many iterations.
+    pub lifetime: Option<usize>,
+    /// The default sink becomes the given one at the given iteration.
+    pub default_sinks: Vec<(usize, Option<String>)>,
+    /// Every move of a source output, as its index and the new source.
+    pub moves: Arc<Mutex<Vec<(u32, String)>>>,
+}
+
+impl ServerScript {
+    /// A server with a single default sink named `name`.
+    pub fn with_sink(name: &str) -> Self {
+        ServerScript {
+            sinks: vec![sink(0, name)],
+            default_sink: Some(name.to_string()),
+            ..ServerScript::default()
+        }
+    }
+}
+
+/// Hands out one scripted connection per connect, in order. Connecting
+/// is refused once the scripts run out.
+pub struct FakeConnector {
+    attempts: Mutex<VecDeque<Result<ServerScript, PAErr>>>,
+}
+
+impl FakeConnector {
+    pub fn new(attempts: Vec<Result<ServerScript, PAErr>>) -> Self {
+        FakeConnector {
+            attempts: Mutex::new(attempts.into()),
+        }
+    }
+}
+
+impl Connector for FakeConnector {
+    type Server = FakeServer;
+
+    fn connect(&self) -> Result<FakeServer, Error> {
+        match self.attempts.lock().unwrap().pop_front() {
+            Some(Ok(script)) => Ok(FakeServer {
+                default_sink: RefCell::new(script.default_sink.clone()),
+                script,
+                iterations: Cell::new(0),
+                watching: Cell::new(false),
+                default_changed: Cell::new(false),
+            }),
+            Some(Err(err)) => Err(Error::Connection(err)),
+            None => Err(Error::Connection(PAErr::from(Code::ConnectionRefused))),
+        }
+    }
+}
+
+pub struct FakeServer {
+    script: ServerScript,
+    iterations: Cell<usize>,
+    default_sink: RefCell<Option<String>>,
+    watching: Cell<bool>,
+    default_changed: Cell<bool>,
+}
+
+impl Server for FakeServer {
+    type Stream = FakeStream;
+
+    fn sinks(&self) -> Result<Vec<Sink>, Error> {
+        Ok(self.script.sinks.clone())
+    }
+
+    fn sources(&self) -> Result<Vec<Source>, Error> {
+        Ok(Vec::new())
+    }
+
+    fn server_defaults(&self) -> Result<ServerDefaults, Error> {
+        Ok(ServerDefaults {
+            sink: self.default_sink.borrow().clone(),
+            source: None,
+        })
+    }
+
+    fn move_source_output(&self, index: u32, source_name: &str) -> Result<(), Error> {
+        let mut moves = self.script.moves.lock().unwrap();
+        moves.push((index, source_name.to_string()));
+        Ok(())
+    }
+
+    fn record(&self, _source: &str, _spec: &Spec, _map: &Map) -> Result<FakeStream, Error> {
+        if let Some(err) = self.script.record_error {
+            return Err(Error::Stream(err));
+        }
+
+        Ok(FakeStream {
+            states: RefCell::new(self.script.states.iter().copied().collect()),
+            reads: self.script.reads.iter().cloned().collect(),
+        })
+    }
+
+    fn watch_default_sink(&self) {
+        self.watching.set(true);
+    }
+
+    fn default_sink_changed(&self) -> bool {
+        self.watching.get() && self.default_changed.replace(false)
+    }
+
+    fn iterate(&self, timeout: Duration) -> Result<(), Error> {
+        let iterations = self.iterations.get() + 1;
+        self.iterations.set(iterations);
+        if self
+            .script
+            .lifetime
+            .is_some_and(|lifetime| iterations > lifetime)
+        {
+            return Err(Error::Disconnected);
+        }
+        for (at, sink) in &self.script.default_sinks {
+            if *at == iterations {
+                *self.default_sink.borrow_mut() = sink.clone();
+                self.default_changed.set(true);
+            }
+        }
+
+        thread::sleep(timeout.min(Duration::from_millis(1)));
+        Ok(())
+    }
+
+    fn disconnect(&self) {}
+}
+
+pub struct FakeStream {
+    states: RefCell<VecDeque<StreamState>>,
+    reads: VecDeque<Result<Peek, PAErr>>,
+}
+
+impl RecordStream for FakeStream {
+    fn state(&self) -> StreamState {
+        let mut states = self.states.borrow_mut();
+        let state = states.front().copied().unwrap_or(StreamState::Ready);
+        if states.len() > 1 {
+            states.pop_front();
+        }
+        state
+    }
+
+    fn read(&mut self) -> Result<Peek, Error> {
+        match self.reads.pop_front() {
+            Some(read) => read.map_err(Error::Stream),
+            None => Ok(Peek::Empty),
+        }
+    }
+
+    fn index(&self) -> Option<u32> {
+        Some(0)
+    }
+
+    fn disconnect(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Collects events from `source` until `done` says so, the source ends or
+/// a few seconds pass.
+pub fn collect(
+    source: &mut dyn AudioSource,
+    done: impl Fn(&[CaptureEvent]) -> bool,
+) -> Vec<CaptureEvent> {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut events = Vec::new();
+
+    while Instant::now() < deadline && !done(&events) {
+        match source.recv_timeout(Duration::from_millis(10)) {
+            Ok(event) => events.push(event),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    events
+}
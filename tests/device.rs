@@ -0,0 +1,127 @@
+mod common;
+
+use common::sink;
+use pulse_visualizer::device::{self, DeviceSelector, SelectFailure, Sink};
+
+fn sinks() -> Vec<Sink> {
+    vec![
+        sink(0, "alsa_output.pci.analog-stereo"),
+        sink(1, "alsa_output.usb-FiiO_K3"),
+        sink(2, "alsa_output.usb-FiiO_K3.2"),
+    ]
+}
+
+fn select(selector: &str, default_sink: Option<&str>) -> Result<String, SelectFailure> {
+    let sinks = sinks();
+    let selector: DeviceSelector = selector.parse().unwrap();
+    selector
+        .select(&sinks, default_sink)
+        .map(|sink| sink.name.clone())
+        .map_err(|err| err.reason)
+}
+
+#[test]
+fn parses_selectors() {
+    assert!(matches!(
+        "@DEFAULT_SINK@".parse(),
+        Ok(DeviceSelector::DefaultSink)
+    ));
+    assert!(matches!(
+        "@DEFAULT_MONITOR@".parse(),
+        Ok(DeviceSelector::DefaultMonitor)
+    ));
+    assert!(matches!("12".parse(), Ok(DeviceSelector::Index(12))));
+    assert!(matches!("/usb/".parse(), Ok(DeviceSelector::Regex(_))));
+    assert!(matches!("FiiO".parse(), Ok(DeviceSelector::Name(_))));
+    assert!("/(/".parse::<DeviceSelector>().is_err());
+}
+
+#[test]
+fn selects_the_default_sink() {
+    assert_eq!(
+        select("@DEFAULT_MONITOR@", Some("alsa_output.usb-FiiO_K3")).unwrap(),
+        "alsa_output.usb-FiiO_K3"
+    );
+    assert!(matches!(
+        select("@DEFAULT_SINK@", None),
+        Err(SelectFailure::NoDefault)
+    ));
+    assert!(matches!(
+        select("@DEFAULT_SINK@", Some("gone")),
+        Err(SelectFailure::NoMatch)
+    ));
+}
+
+#[test]
+fn selects_by_index() {
+    assert_eq!(select("2", None).unwrap(), "alsa_output.usb-FiiO_K3.2");
+    assert!(matches!(select("7", None), Err(SelectFailure::NoMatch)));
+}
+
+#[test]
+fn exact_name_wins_over_substring() {
+    assert_eq!(
+        select("alsa_output.usb-FiiO_K3", None).unwrap(),
+        "alsa_output.usb-FiiO_K3"
+    );
+}
+
+#[test]
+fn selects_by_unique_substring() {
+    assert_eq!(
+        select("pci", None).unwrap(),
+        "alsa_output.pci.analog-stereo"
+    );
+    assert!(matches!(select("HDMI", None), Err(SelectFailure::NoMatch)));
+}
+
+#[test]
+fn ambiguous_substring_lists_matches() {
+    match select("FiiO", None) {
+        Err(SelectFailure::Ambiguous(names)) => assert_eq!(
+            names,
+            ["alsa_output.usb-FiiO_K3", "alsa_output.usb-FiiO_K3.2"]
+        ),
+        other => panic!("expected an ambiguous match, got {:?}", other),
+    }
+}
+
+#[test]
+fn selects_first_regex_match() {
+    assert_eq!(select("/FiiO/", None).unwrap(), "alsa_output.usb-FiiO_K3");
+    assert_eq!(
+        select("/\\.2$/", None).unwrap(),
+        "alsa_output.usb-FiiO_K3.2"
+    );
+}
+
+#[test]
+fn select_error_lists_available_sinks() {
+    let sinks = sinks();
+    let err = DeviceSelector::Name("HDMI".to_string())
+        .select(&sinks, None)
+        .unwrap_err();
+
+    let message = err.to_string();
+    assert!(message.starts_with("No sink matches \"HDMI\"."));
+    for sink in &sinks {
+        assert!(message.contains(&sink.name));
+    }
+}
+
+#[test]
+fn resolves_the_monitor_source() {
+    let sinks = sinks();
+    let (sink, monitor) = device::resolve_monitor(&DeviceSelector::Index(1), &sinks, None).unwrap();
+    assert_eq!(sink.index, 1);
+    assert_eq!(monitor, "alsa_output.usb-FiiO_K3.monitor");
+}
+
+#[test]
+fn sink_without_monitor_is_rejected() {
+    let mut sinks = sinks();
+    sinks[0].monitor_source_name = None;
+
+    let err = device::resolve_monitor(&DeviceSelector::Index(0), &sinks, None).unwrap_err();
+    assert!(matches!(err.reason, SelectFailure::NoMonitor));
+}
@@ -0,0 +1,28 @@
+use log::LevelFilter;
+use pulse_visualizer::logging::LogFilter;
+
+#[test]
+fn parses_filters() {
+    let filter: LogFilter = "info,stream=debug, render=off".parse().unwrap();
+    assert_eq!(filter.default, LevelFilter::Info);
+    assert_eq!(filter.level("stream"), LevelFilter::Debug);
+    assert_eq!(filter.level("render"), LevelFilter::Off);
+    assert_eq!(filter.level("context"), LevelFilter::Info);
+
+    let filter: LogFilter = "context=trace".parse().unwrap();
+    assert_eq!(filter.default, LevelFilter::Warn);
+    assert_eq!(filter.level("context"), LevelFilter::Trace);
+}
+
+#[test]
+fn later_levels_win() {
+    let filter: LogFilter = "error,stream=info,warn,stream=trace".parse().unwrap();
+    assert_eq!(filter.default, LevelFilter::Warn);
+    assert_eq!(filter.level("stream"), LevelFilter::Trace);
+}
+
+#[test]
+fn rejects_unknown_levels() {
+    assert!("loud".parse::<LogFilter>().is_err());
+    assert!("stream=verbose".parse::<LogFilter>().is_err());
+}
@@ -0,0 +1,66 @@
+use std::f32::consts::TAU;
+
+use pulse::channelmap::Position;
+use pulse_visualizer::{analysis::loudness::LoudnessMeter, decode::AudioBlock};
+
+const RATE: u32 = 48000;
+
+/// Feeds `seconds` of a stereo 1 kHz sine peaking at `dbfs` to `meter`, in
+/// blocks of 10 ms.
+fn sine(meter: &mut LoudnessMeter, dbfs: f32, seconds: usize) {
+    let amplitude = 10f32.powf(dbfs / 20.0);
+    let block_len = RATE as usize / 100;
+    for block in 0..seconds * 100 {
+        let samples: Vec<f32> = (0..block_len)
+            .map(|frame| {
+                let time = (block * block_len + frame) as f32 / RATE as f32;
+                amplitude * (TAU * 1000.0 * time).sin()
+            })
+            .collect();
+        let block = AudioBlock {
+            rate: RATE,
+            positions: vec![Position::FrontLeft, Position::FrontRight],
+            channels: vec![samples.clone(), samples],
+        };
+        meter.push(&block, |_| {});
+    }
+}
+
+#[test]
+fn steady_sine_reads_its_level() {
+    let mut meter = LoudnessMeter::new(-23.0);
+    sine(&mut meter, -20.0, 5);
+
+    // Two channels at -23 dB mean square each, and the gain of the
+    // K-weighting at 1 kHz cancels the -0.691 dB of BS.1770.
+    let reading = meter.reading();
+    for value in [reading.momentary, reading.short_term, reading.integrated] {
+        let value = value.unwrap();
+        assert!((value - -20.0).abs() < 0.1, "read {} LUFS", value);
+    }
+    assert!(meter.range().unwrap() < 0.2);
+}
+
+#[test]
+fn range_spans_loud_and_quiet_parts() {
+    // EBU Tech 3342, first test signal: 20 s at -20 dBFS, then 20 s at -30.
+    let mut meter = LoudnessMeter::new(-23.0);
+    sine(&mut meter, -20.0, 20);
+    sine(&mut meter, -30.0, 20);
+
+    let range = meter.range().unwrap();
+    assert!((range - 10.0).abs() < 1.0, "range of {} LU", range);
+    let summary = meter.summary();
+    assert_eq!(summary.duration, 40.0);
+    assert!((summary.max_momentary.unwrap() - -20.0).abs() < 0.1);
+}
+
+#[test]
+fn silence_has_no_integrated_loudness() {
+    let mut meter = LoudnessMeter::new(-23.0);
+    sine(&mut meter, -100.0, 5);
+
+    assert_eq!(meter.integrated(), None);
+    assert_eq!(meter.range(), None);
+    assert!(meter.momentary().is_some());
+}
@@ -0,0 +1,51 @@
+use pulse::channelmap::Position;
+use pulse_visualizer::{
+    analysis::spectrum::{SpectrumAnalyzer, SpectrumConfig},
+    decode::AudioBlock,
+};
+
+/// A mono block counting up from `start`, one per sample.
+fn ramp(start: usize, len: usize) -> AudioBlock {
+    AudioBlock {
+        rate: 48000,
+        positions: vec![Position::Mono],
+        channels: vec![(start..start + len).map(|sample| sample as f32).collect()],
+    }
+}
+
+/// Feeds a ramp of `total` samples in blocks of `block` and returns where
+/// each frame starts. The DC bin is the frame's mean weighted by the
+/// window, which lies half a frame after its start.
+fn frame_starts(config: SpectrumConfig, total: usize, block: usize) -> Vec<usize> {
+    let mut analyzer = SpectrumAnalyzer::new(config);
+    let mut starts = Vec::new();
+    for start in (0..total).step_by(block) {
+        analyzer.push(&ramp(start, block), |spectrum| {
+            let mean = spectrum.magnitudes[0].round() as usize;
+            starts.push(mean - spectrum.fft_size / 2);
+        });
+    }
+    starts
+}
+
+#[test]
+fn overlapping_frames_start_every_hop() {
+    let config = SpectrumConfig {
+        fft_size: 16,
+        hop_size: 8,
+        ..SpectrumConfig::default()
+    };
+    assert_eq!(frame_starts(config, 40, 5), [0, 8, 16, 24]);
+}
+
+#[test]
+fn hops_longer_than_a_frame_skip_across_blocks() {
+    let config = SpectrumConfig {
+        fft_size: 16,
+        hop_size: 40,
+        ..SpectrumConfig::default()
+    };
+    // However the samples are split up.
+    assert_eq!(frame_starts(config, 100, 20), [0, 40, 80]);
+    assert_eq!(frame_starts(config, 100, 7), [0, 40, 80]);
+}